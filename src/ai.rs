@@ -0,0 +1,188 @@
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+
+use crate::board::Board;
+use crate::input::Action;
+use crate::{GameMode, GameState, PieceType};
+
+// Board evaluation weights: favour clears, punish height, holes and a ragged surface
+const HEIGHT_WEIGHT: f64 = -0.51;
+const LINES_WEIGHT: f64 = 0.76;
+const HOLES_WEIGHT: f64 = -0.36;
+const BUMPINESS_WEIGHT: f64 = -0.18;
+// Random noise added to each candidate's score at level 1, shrinking to none at level 10
+const MAX_NOISE: f64 = 6.0;
+
+// Where the AI wants the current piece: clockwise turns from spawn, then column
+#[derive(Clone, Copy)]
+pub struct AiMove {
+    rotations: usize,
+    x: i32,
+}
+
+// Every rotation and column the shape can be hard dropped into from row
+// `y`, with the board it leaves behind and how many lines that cleared
+fn drops(board: &Board, shape: &[Vec<bool>], y: i32, piece_type: PieceType) -> Vec<(AiMove, Board, u32)> {
+    let mut results = Vec::new();
+    for (rotations, shape) in GameState::all_rotations(shape, piece_type).into_iter().enumerate() {
+        let width = shape[0].len() as i32;
+        for x in 0..=(board.width() as i32 - width) {
+            if !board.shape_fits(&shape, x, y) {
+                continue;
+            }
+            let mut landing = y;
+            while board.shape_fits(&shape, x, landing + 1) {
+                landing += 1;
+            }
+            let mut after = board.clone();
+            after.place(&shape, x, landing, piece_type);
+            let lines = after.clear_rows();
+            results.push((AiMove { rotations, x }, after, lines));
+        }
+    }
+    results
+}
+
+fn evaluate(board: &Board, lines: u32) -> f64 {
+    let aggregate_height = (0..board.width()).map(|x| board.column_height(x)).sum::<usize>();
+    let holes = board.count_covered_empty_cells();
+    let bumpiness = board.surface_entropy();
+
+    HEIGHT_WEIGHT * aggregate_height as f64
+        + LINES_WEIGHT * lines as f64
+        + HOLES_WEIGHT * holes as f64
+        + BUMPINESS_WEIGHT * bumpiness as f64
+}
+
+// Picks a move for the current piece. With `lookahead` each drop is scored by
+// the best follow-up with the next piece; `noise` blurs the scores so weaker
+// players make mistakes.
+pub fn best_move(game: &GameState, lookahead: bool, noise: f64, rng: &mut StdRng) -> Option<AiMove> {
+    let piece = &game.current_piece;
+    let next = game.sequence.peek(1)[0];
+    let next_shape = GameState::get_piece_shape(next);
+
+    drops(&game.board, &piece.shape, piece.y, piece.piece_type)
+        .into_iter()
+        .map(|(ai_move, after, lines)| {
+            let mut score = evaluate(&after, lines);
+            if lookahead {
+                score = drops(&after, &next_shape, 0, next)
+                    .iter()
+                    .map(|(_, after_next, next_lines)| evaluate(after_next, lines + next_lines))
+                    .fold(f64::NEG_INFINITY, f64::max);
+            }
+            if noise > 0.0 {
+                score += rng.gen_range(-noise..noise);
+            }
+            (ai_move, score)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(ai_move, _)| ai_move)
+}
+
+// Computer opponent for GameMode::VersusCpu. It plays its own game one
+// simulated key press at a time, so it's only as fast as its level allows.
+pub struct CpuPlayer {
+    pub game: GameState,
+    pub level: u8,
+    plan: Option<AiMove>,
+    planned_piece: u32,  // pieces_placed when the plan was made, to spot pieces that locked early
+    next_press: f64,  // Simulated time of the next key press
+    rng: StdRng,
+}
+
+impl CpuPlayer {
+    pub fn new(cols: usize, rows: usize, level: u8) -> Self {
+        let mut game = GameState::new(cols, rows);
+        game.set_mode(GameMode::VersusCpu { cpu_level: level });
+        Self {
+            game,
+            level,
+            plan: None,
+            planned_piece: 0,
+            next_press: 0.0,
+            rng: StdRng::seed_from_u64(::rand::thread_rng().gen()),
+        }
+    }
+
+    // Key presses per second
+    fn kps(&self) -> f64 {
+        self.level as f64 * 2.0
+    }
+
+    fn noise(&self) -> f64 {
+        MAX_NOISE * (10 - self.level.min(10)) as f64 / 9.0
+    }
+
+    // Presses at most one key, then advances the CPU's game by one tick
+    pub fn tick(&mut self) {
+        if self.game.game_over {
+            return;
+        }
+        let now = self.game.sim_time();
+        if now >= self.next_press {
+            self.next_press = now + 1.0 / self.kps();
+            self.press_key();
+        }
+        if !self.game.game_over {
+            self.game.tick();
+        }
+        // Only the player's game is heard, or stops for a tetris
+        self.game.sound_events.clear();
+        self.game.tetris_animation = None;
+    }
+
+    // Works the current piece one step towards the planned move: turn, then
+    // shift, then hard drop
+    fn press_key(&mut self) {
+        if self.planned_piece != self.game.stats.pieces_placed {
+            self.plan = None;
+        }
+        if self.plan.is_none() {
+            self.planned_piece = self.game.stats.pieces_placed;
+            self.plan = best_move(&self.game, self.level >= 10, self.noise(), &mut self.rng);
+        }
+        let Some(plan) = self.plan.as_mut() else {
+            return;
+        };
+
+        if plan.rotations > 0 {
+            plan.rotations -= 1;
+            self.press(Action::Rotate);
+            return;
+        }
+
+        let x = self.game.current_piece.x;
+        let shift = match plan.x.cmp(&x) {
+            std::cmp::Ordering::Less => Some(Action::MoveLeft),
+            std::cmp::Ordering::Greater => Some(Action::MoveRight),
+            std::cmp::Ordering::Equal => None,
+        };
+        if let Some(shift) = shift {
+            self.press(shift);
+            // A blocked shift means the plan went stale, so drop where it is
+            if self.game.current_piece.x != x {
+                return;
+            }
+        }
+        // A delayed hard drop lock keeps the plan until the piece really locks
+        if self.press(Action::HardDrop) {
+            self.plan = None;
+        }
+    }
+
+    // Presses `action` right now. Returns true if that locked the piece.
+    fn press(&mut self, action: Action) -> bool {
+        let now = self.game.sim_time();
+        !self.game.apply_batch(vec![(now, action)]).is_empty()
+    }
+}
+
+// Hands any garbage each side has sent over to the other
+pub fn exchange_garbage(player: &mut GameState, cpu: &mut GameState) {
+    let to_cpu = player.take_outgoing_garbage();
+    let to_player = cpu.take_outgoing_garbage();
+    cpu.receive_garbage(to_cpu);
+    player.receive_garbage(to_player);
+}
@@ -0,0 +1,75 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use macroquad::prelude::*;
+
+// A pending macroquad `load_*` call
+type LoadTask = Pin<Box<dyn Future<Output = Result<Texture2D, macroquad::Error>>>>;
+
+// Optional artwork. Anything missing from the assets folder is simply left out.
+#[derive(Default)]
+pub struct Assets {
+    pub background: Option<Texture2D>,
+}
+
+// Drives the asset loads a frame at a time so a loading screen can be shown
+// meanwhile
+pub struct AssetLoader {
+    tasks: Vec<(String, LoadTask)>,  // Loads still in flight, by asset name
+    completed: usize,
+    assets: Assets,
+}
+
+impl AssetLoader {
+    pub fn new() -> Self {
+        let tasks: Vec<(String, LoadTask)> = vec![
+            ("background".to_string(), Box::pin(load_texture("assets/background.png"))),
+        ];
+        Self { tasks, completed: 0, assets: Assets::default() }
+    }
+
+    // Polls every unfinished load once. Returns true when all are done.
+    pub fn poll(&mut self) -> bool {
+        let mut context = Context::from_waker(Waker::noop());
+        let mut i = 0;
+        while i < self.tasks.len() {
+            let Poll::Ready(result) = self.tasks[i].1.as_mut().poll(&mut context) else {
+                i += 1;
+                continue;
+            };
+
+            let (name, _) = self.tasks.remove(i);
+            self.completed += 1;
+            // A failed load is a missing optional asset, not an error
+            match result {
+                Ok(texture) if name == "background" => self.assets.background = Some(texture),
+                Ok(_) => {}
+                Err(err) => eprintln!("No {} asset: {}", name, err),
+            }
+        }
+        self.tasks.is_empty()
+    }
+
+    pub fn draw(&self) {
+        clear_background(BLACK);
+        let (cx, cy) = (screen_width() / 2.0, screen_height() / 2.0);
+
+        // Ring of dots with a bright head going round
+        let head = (get_time() * 8.0) as usize % 8;
+        for i in 0..8 {
+            let angle = i as f32 * std::f32::consts::TAU / 8.0;
+            let alpha = 1.0 - ((head + 8 - i) % 8) as f32 / 8.0;
+            draw_circle(cx + angle.cos() * 20.0, cy - 30.0 + angle.sin() * 20.0, 4.0, Color::new(1.0, 1.0, 1.0, alpha));
+        }
+
+        let total = self.completed + self.tasks.len();
+        let text = format!("Loading assets... {}/{}", self.completed, total);
+        let size = measure_text(&text, None, 20, 1.0);
+        draw_text(&text, cx - size.width / 2.0, cy + 20.0, 20.0, LIGHTGRAY);
+    }
+
+    pub fn finish(self) -> Assets {
+        self.assets
+    }
+}
@@ -0,0 +1,34 @@
+// Most garbage any clear sends: a Tetris. There are no combo or back-to-back
+// bonuses, so nothing goes above it.
+pub const MAX_ATTACK: u32 = 4;
+
+// Most garbage a single clear sends by default. One short of a Tetris, so a
+// single big clear can't decide a versus game on its own; raise it to
+// MAX_ATTACK to play the table uncut.
+pub const DEFAULT_ATTACK_CAP: u32 = 3;
+
+// Garbage lines sent to the opponent for clearing `lines` rows at once, never
+// more than `cap`
+pub fn attack_for_clear(lines: u32, cap: u32) -> u32 {
+    let attack = match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => MAX_ATTACK,
+    };
+    attack.min(cap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_cap_cuts_a_tetris_short() {
+        assert_eq!(attack_for_clear(4, DEFAULT_ATTACK_CAP), DEFAULT_ATTACK_CAP);
+        assert_eq!(attack_for_clear(4, MAX_ATTACK), MAX_ATTACK);
+        assert_eq!(attack_for_clear(3, DEFAULT_ATTACK_CAP), 2);
+        assert_eq!(attack_for_clear(3, 1), 1);
+        assert_eq!(attack_for_clear(1, MAX_ATTACK), 0);
+    }
+}
@@ -0,0 +1,159 @@
+use std::f32::consts::TAU;
+
+use macroquad::prelude::*;
+
+// Animated backdrops, one per level, cycling every 10 levels. Each pattern is
+// drawn dim and slow so it never competes with the board.
+
+// Colors each pattern is built from, by `level % 10`
+const PALETTES: [[Color; 3]; 10] = [
+    [Color::new(0.10, 0.20, 0.45, 1.0), Color::new(0.15, 0.35, 0.60, 1.0), Color::new(0.05, 0.10, 0.25, 1.0)],
+    [Color::new(0.45, 0.15, 0.10, 1.0), Color::new(0.60, 0.30, 0.10, 1.0), Color::new(0.25, 0.05, 0.05, 1.0)],
+    [Color::new(0.10, 0.40, 0.20, 1.0), Color::new(0.20, 0.55, 0.30, 1.0), Color::new(0.05, 0.20, 0.10, 1.0)],
+    [Color::new(0.35, 0.10, 0.45, 1.0), Color::new(0.50, 0.20, 0.60, 1.0), Color::new(0.15, 0.05, 0.25, 1.0)],
+    [Color::new(0.45, 0.40, 0.10, 1.0), Color::new(0.60, 0.55, 0.15, 1.0), Color::new(0.25, 0.20, 0.05, 1.0)],
+    [Color::new(0.10, 0.40, 0.45, 1.0), Color::new(0.15, 0.55, 0.60, 1.0), Color::new(0.05, 0.20, 0.25, 1.0)],
+    [Color::new(0.45, 0.10, 0.30, 1.0), Color::new(0.60, 0.20, 0.40, 1.0), Color::new(0.25, 0.05, 0.15, 1.0)],
+    [Color::new(0.25, 0.25, 0.30, 1.0), Color::new(0.40, 0.40, 0.45, 1.0), Color::new(0.12, 0.12, 0.15, 1.0)],
+    [Color::new(0.15, 0.30, 0.10, 1.0), Color::new(0.45, 0.45, 0.10, 1.0), Color::new(0.05, 0.15, 0.05, 1.0)],
+    [Color::new(0.30, 0.15, 0.05, 1.0), Color::new(0.10, 0.20, 0.40, 1.0), Color::new(0.15, 0.08, 0.02, 1.0)],
+];
+
+// How strongly patterns show through, before each one's own fading
+const BACKGROUND_ALPHA: f32 = 0.25;
+
+fn faded(color: Color, alpha: f32) -> Color {
+    Color::new(color.r, color.g, color.b, alpha * BACKGROUND_ALPHA)
+}
+
+// Fills the window with the pattern for `level` as it looks `t` seconds in
+pub fn draw_level_background(level: u32, t: f64) {
+    let index = (level % 10) as usize;
+    let palette = PALETTES[index];
+    let t = t as f32;
+    let (w, h) = (screen_width(), screen_height());
+    draw_rectangle(0.0, 0.0, w, h, faded(palette[2], 1.0));
+    match index {
+        0 => rotating_polygons(palette, t, w, h),
+        1 => pulsing_circles(palette, t, w, h),
+        2 => drifting_stripes(palette, t, w, h),
+        3 => orbiting_rings(palette, t, w, h),
+        4 => breathing_grid(palette, t, w, h),
+        5 => rising_bubbles(palette, t, w, h),
+        6 => spinning_star(palette, t, w, h),
+        7 => sliding_checker(palette, t, w, h),
+        8 => waving_columns(palette, t, w, h),
+        _ => spiral(palette, t, w, h),
+    }
+}
+
+// Hexagons on a loose grid, each turning slowly
+fn rotating_polygons(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    for row in 0..5 {
+        for col in 0..6 {
+            let x = (col as f32 + 0.5) * w / 6.0;
+            let y = (row as f32 + 0.5) * h / 5.0;
+            let color = if (row + col) % 2 == 0 { palette[0] } else { palette[1] };
+            draw_poly(x, y, 6, w / 16.0, t * 10.0 + (row * col) as f32 * 15.0, faded(color, 0.8));
+        }
+    }
+}
+
+// Concentric circles swelling and shrinking from the center
+fn pulsing_circles(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let max = w.max(h) * 0.7;
+    for ring in (0..6).rev() {
+        let radius = max * (ring as f32 + 1.0 + (t * 0.3).sin() * 0.3) / 6.0;
+        draw_circle(w / 2.0, h / 2.0, radius, faded(palette[ring % 2], 0.5));
+    }
+}
+
+// Diagonal-feeling bands scrolling sideways
+fn drifting_stripes(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let band = w / 8.0;
+    let shift = (t * 10.0) % (band * 2.0);
+    let mut x = -band * 2.0 + shift;
+    let mut i = 0;
+    while x < w {
+        draw_rectangle(x, 0.0, band, h, faded(palette[i % 2], 0.6));
+        x += band;
+        i += 1;
+    }
+}
+
+// Dots circling the center at different speeds
+fn orbiting_rings(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    for orbit in 1..=4 {
+        let radius = orbit as f32 * w.min(h) / 9.0;
+        for i in 0..orbit * 4 {
+            let angle = i as f32 / (orbit * 4) as f32 * TAU + t * 0.2 / orbit as f32;
+            let (x, y) = (w / 2.0 + angle.cos() * radius, h / 2.0 + angle.sin() * radius);
+            draw_circle(x, y, 6.0 + orbit as f32 * 2.0, faded(palette[orbit % 2], 0.9));
+        }
+    }
+}
+
+// Squares on a grid growing and shrinking in a wave
+fn breathing_grid(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let cell = w / 10.0;
+    for row in 0..(h / cell) as i32 + 1 {
+        for col in 0..10 {
+            let scale = 0.5 + 0.4 * (t * 0.5 + (row + col) as f32 * 0.4).sin();
+            let size = cell * scale;
+            let (x, y) = (col as f32 * cell + (cell - size) / 2.0, row as f32 * cell + (cell - size) / 2.0);
+            draw_rectangle(x, y, size, size, faded(palette[(row + col) as usize % 2], 0.6));
+        }
+    }
+}
+
+// Circles floating up from the bottom and wrapping around
+fn rising_bubbles(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    for i in 0..16 {
+        let x = (i as f32 * 0.618).fract() * w;
+        let speed = 10.0 + (i % 5) as f32 * 4.0;
+        let y = h - (t * speed + i as f32 * 60.0) % (h + 80.0) + 40.0;
+        draw_circle(x, y, 10.0 + (i % 4) as f32 * 8.0, faded(palette[i % 2], 0.7));
+    }
+}
+
+// A big star turning in the middle, fading in and out
+fn spinning_star(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let alpha = 0.5 + 0.3 * (t * 0.25).sin();
+    let size = w.min(h) * 0.45;
+    draw_poly(w / 2.0, h / 2.0, 3, size, t * 5.0, faded(palette[0], alpha));
+    draw_poly(w / 2.0, h / 2.0, 3, size, t * 5.0 + 60.0, faded(palette[1], alpha));
+}
+
+// Checkerboard drifting diagonally
+fn sliding_checker(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let cell = w / 8.0;
+    let shift = (t * 6.0) % (cell * 2.0);
+    for row in -2..(h / cell) as i32 + 2 {
+        for col in -2..10 {
+            if (row + col) % 2 == 0 {
+                let (x, y) = (col as f32 * cell + shift, row as f32 * cell + shift);
+                draw_rectangle(x, y, cell, cell, faded(palette[0], 0.6));
+            }
+        }
+    }
+}
+
+// Columns rising and falling like a slow equalizer
+fn waving_columns(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let width = w / 12.0;
+    for col in 0..12 {
+        let height = h * (0.4 + 0.25 * (t * 0.4 + col as f32 * 0.5).sin());
+        draw_rectangle(col as f32 * width, h - height, width - 2.0, height, faded(palette[col % 2], 0.6));
+    }
+}
+
+// Dots along a spiral arm turning and breathing in size
+fn spiral(palette: [Color; 3], t: f32, w: f32, h: f32) {
+    let scale = 1.0 + 0.15 * (t * 0.2).sin();
+    for i in 0..60 {
+        let angle = i as f32 * 0.35 + t * 0.15;
+        let radius = i as f32 * w.min(h) / 110.0 * scale;
+        let (x, y) = (w / 2.0 + angle.cos() * radius, h / 2.0 + angle.sin() * radius);
+        draw_circle(x, y, 3.0 + i as f32 * 0.15, faded(palette[i % 2], 0.8));
+    }
+}
@@ -0,0 +1,487 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use macroquad::prelude::*;
+
+use crate::{Piece, PieceType};
+
+pub const GRID_COLS: usize = 10;
+pub const GRID_ROWS: usize = 20;
+
+// Neutral gray for garbage, distinct from all seven piece colors
+const GARBAGE_COLOR: Color = Color::new(0.45, 0.45, 0.45, 1.0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Piece(PieceType),  // Left behind by a locked piece
+    Garbage,  // Raised from below; doesn't belong to any piece
+}
+
+impl Cell {
+    pub fn is_filled(&self) -> bool {
+        *self != Cell::Empty
+    }
+
+    pub fn color(&self) -> Option<Color> {
+        match self {
+            Cell::Empty => None,
+            Cell::Piece(piece_type) => Some(piece_type.get_color()),
+            Cell::Garbage => Some(GARBAGE_COLOR),
+        }
+    }
+
+    // `.` for empty, the piece letter for piece cells and `#` for garbage
+    pub fn letter(&self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Piece(piece_type) => piece_type.letter(),
+            Cell::Garbage => '#',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Cell> {
+        match letter {
+            '.' => Some(Cell::Empty),
+            '#' => Some(Cell::Garbage),
+            letter => PieceType::from_letter(letter).map(Cell::Piece),
+        }
+    }
+}
+
+// A stored board that doesn't fit the board size being played
+#[derive(Debug)]
+pub struct SizeMismatch {
+    pub expected: (usize, usize),
+    pub found: (usize, usize),
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "board is {}x{} but this game is {}x{}",
+            self.found.0, self.found.1, self.expected.0, self.expected.1
+        )
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+// Changes to `grid` go through Board's own methods, which mark the cached
+// surface dirty
+#[derive(Clone)]
+pub struct Board {
+    pub grid: Vec<Vec<Cell>>,
+    // Simulated time each cell's piece locked, alongside `grid`. Only read for
+    // filled cells; 0 for garbage and anything not stamped.
+    placed_at: Vec<Vec<f64>>,
+    surface: RefCell<Vec<(usize, usize)>>,  // Cached column_surface_cells
+    surface_dirty: std::cell::Cell<bool>,
+}
+
+impl Board {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            grid: vec![vec![Cell::Empty; cols]; rows],
+            placed_at: vec![vec![0.0; cols]; rows],
+            surface: RefCell::new(Vec::new()),
+            surface_dirty: std::cell::Cell::new(true),
+        }
+    }
+
+    // (col, row) of the topmost filled cell in each column, or (col, height)
+    // for an empty column
+    pub fn column_surface_cells(&self) -> Vec<(usize, usize)> {
+        self.refresh_surface();
+        self.surface.borrow().clone()
+    }
+
+    fn refresh_surface(&self) {
+        if !self.surface_dirty.get() {
+            return;
+        }
+        let surface = (0..self.width())
+            .map(|col| (col, self.grid.iter().position(|row| row[col].is_filled()).unwrap_or(self.height())))
+            .collect();
+        *self.surface.borrow_mut() = surface;
+        self.surface_dirty.set(false);
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    // Checks a stored board against the `cols` x `rows` size in play before
+    // it's put back into a game
+    pub fn check_size(&self, cols: usize, rows: usize) -> Result<(), SizeMismatch> {
+        if (self.width(), self.height()) == (cols, rows) {
+            Ok(())
+        } else {
+            Err(SizeMismatch { expected: (cols, rows), found: (self.width(), self.height()) })
+        }
+    }
+
+    pub fn can_move(&self, piece: &Piece, new_x: i32, new_y: i32) -> bool {
+        self.shape_fits(&piece.shape, new_x, new_y)
+    }
+
+    pub fn shape_fits(&self, shape: &[Vec<bool>], new_x: i32, new_y: i32) -> bool {
+        for (row_idx, row) in shape.iter().enumerate() {
+            for (col_idx, &cell) in row.iter().enumerate() {
+                if cell {
+                    let grid_x = new_x + col_idx as i32;
+                    let grid_y = new_y + row_idx as i32;
+
+                    if !(0..self.width() as i32).contains(&grid_x) || grid_y >= self.height() as i32 {
+                        return false;
+                    }
+
+                    if grid_y >= 0 && self.grid[grid_y as usize][grid_x as usize].is_filled() {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // Writes the shape's cells into the grid. Cells above the top are dropped.
+    pub fn place(&mut self, shape: &[Vec<bool>], x: i32, y: i32, piece_type: PieceType) {
+        self.surface_dirty.set(true);
+        for (row_idx, row) in shape.iter().enumerate() {
+            for (col_idx, &cell) in row.iter().enumerate() {
+                if cell {
+                    let grid_x = x + col_idx as i32;
+                    let grid_y = y + row_idx as i32;
+
+                    if grid_y >= 0 {
+                        self.grid[grid_y as usize][grid_x as usize] = Cell::Piece(piece_type);
+                    }
+                }
+            }
+        }
+    }
+
+    // Records `time` as when the shape's cells were placed, for `placed_at`
+    pub fn stamp(&mut self, shape: &[Vec<bool>], x: i32, y: i32, time: f64) {
+        for (row_idx, row) in shape.iter().enumerate() {
+            for (col_idx, &cell) in row.iter().enumerate() {
+                let grid_y = y + row_idx as i32;
+                if cell && grid_y >= 0 {
+                    self.placed_at[grid_y as usize][(x + col_idx as i32) as usize] = time;
+                }
+            }
+        }
+    }
+
+    // When the piece in cell (x, y) locked, if it was stamped
+    pub fn placed_at(&self, x: usize, y: usize) -> Option<f64> {
+        self.placed_at.get(y).and_then(|row| row.get(x)).copied().filter(|&time| time > 0.0)
+    }
+
+    // Removes completed rows and returns how many were cleared
+    pub fn clear_rows(&mut self) -> u32 {
+        self.surface_dirty.set(true);
+        let mut row = self.height() - 1; // Start from bottom row
+        let mut cleared = 0;
+
+        while row > 0 {
+            if self.grid[row].iter().all(|cell| cell.is_filled()) {
+                // Remove the completed row
+                for r in (1..=row).rev() {
+                    self.grid[r] = self.grid[r-1].clone();
+                    self.placed_at[r] = self.placed_at[r-1].clone();
+                }
+                // Add new empty row at top
+                self.grid[0] = vec![Cell::Empty; self.width()];
+                self.placed_at[0] = vec![0.0; self.width()];
+                cleared += 1;
+            } else {
+                row -= 1;
+            }
+        }
+        cleared
+    }
+
+    // Completed rows by index, i.e. what the next `clear_rows` removes
+    pub fn full_rows(&self) -> Vec<(usize, Vec<Cell>)> {
+        self.grid
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, row)| row.iter().all(|cell| cell.is_filled()))
+            .map(|(y, row)| (y, row.clone()))
+            .collect()
+    }
+
+    // Completed rows with garbage in them, i.e. how many rows the next
+    // `clear_rows` digs out
+    pub fn count_dig_rows(&self) -> u32 {
+        self.grid
+            .iter()
+            .filter(|row| row.iter().all(|cell| cell.is_filled()) && row.contains(&Cell::Garbage))
+            .count() as u32
+    }
+
+    pub fn fill_garbage(&mut self, x: usize, y: usize) {
+        self.surface_dirty.set(true);
+        self.grid[y][x] = Cell::Garbage;
+    }
+
+    // Removes empty rows from inside the stack, dropping everything above them.
+    // Returns the number of rows removed.
+    pub fn gravity_compress(&mut self) -> u32 {
+        self.surface_dirty.set(true);
+        let Some(top) = self.grid.iter().position(|row| row.iter().any(|cell| cell.is_filled())) else {
+            return 0;
+        };
+
+        // Each row keeps its lock times as it drops
+        let (filled, filled_at): (Vec<_>, Vec<_>) = self.grid[top..]
+            .iter()
+            .zip(&self.placed_at[top..])
+            .filter(|(row, _)| row.iter().any(|cell| cell.is_filled()))
+            .map(|(row, placed)| (row.clone(), placed.clone()))
+            .unzip();
+        let removed = self.height() - top - filled.len();
+
+        let width = self.width();
+        let empty = self.height() - filled.len();
+        self.grid = vec![vec![Cell::Empty; width]; empty];
+        self.grid.extend(filled);
+        self.placed_at = vec![vec![0.0; width]; empty];
+        self.placed_at.extend(filled_at);
+
+        removed as u32
+    }
+
+    // Shifts the whole stack up one row and fills the bottom row with garbage,
+    // leaving the `gaps` columns empty. Returns false if a filled cell was
+    // pushed off the top.
+    pub fn push_garbage_row(&mut self, gaps: &[usize]) -> bool {
+        self.surface_dirty.set(true);
+        let overflowed = self.grid[0].iter().any(|cell| cell.is_filled());
+
+        self.grid.remove(0);
+        self.placed_at.remove(0);
+        self.placed_at.push(vec![0.0; self.width()]);
+        let mut row = vec![Cell::Garbage; self.width()];
+        for &col in gaps {
+            row[col] = Cell::Empty;
+        }
+        self.grid.push(row);
+
+        !overflowed
+    }
+
+    // Replaces the whole grid with `rows` at the bottom and empty rows above.
+    // The rows must be as wide as the board and no more than it is tall.
+    pub fn load_field(&mut self, rows: Vec<Vec<Cell>>) {
+        self.surface_dirty.set(true);
+        let width = self.width();
+        let empty = self.height() - rows.len();
+        self.grid = vec![vec![Cell::Empty; width]; empty];
+        self.grid.extend(rows);
+        // Loaded cells were never locked by a piece in this game
+        for row in &mut self.placed_at[empty..] {
+            *row = vec![0.0; width];
+        }
+    }
+
+    // Empties the top `count` rows, leaving everything below where it is
+    pub fn clear_top_rows(&mut self, count: usize) {
+        self.surface_dirty.set(true);
+        let width = self.width();
+        for row in self.grid.iter_mut().take(count) {
+            *row = vec![Cell::Empty; width];
+        }
+        for row in self.placed_at.iter_mut().take(count) {
+            *row = vec![0.0; width];
+        }
+    }
+
+    // Flips the board horizontally, column 0 <-> column 9
+    pub fn mirror(&mut self) {
+        self.surface_dirty.set(true);
+        for row in self.grid.iter_mut() {
+            row.reverse();
+        }
+        for row in self.placed_at.iter_mut() {
+            row.reverse();
+        }
+    }
+
+    // The grid as text with row and column numbers, one Cell::letter per cell
+    pub fn debug_string(&self) -> String {
+        let header: String = (0..self.width()).map(|x| char::from_digit(x as u32 % 10, 10).unwrap()).collect();
+        let mut text = format!("    {}\n", header);
+        for (y, row) in self.grid.iter().enumerate() {
+            let cells: String = row.iter().map(Cell::letter).collect();
+            text += &format!("{:>3} {}\n", y, cells);
+        }
+        text
+    }
+
+    // Prints `debug_string` to stderr for quick inspection
+    pub fn print_debug(&self) {
+        eprint!("{}", self.debug_string());
+    }
+
+    // Height of column `col`'s topmost filled cell above the floor, 0 if empty
+    pub fn column_height(&self, col: usize) -> usize {
+        self.refresh_surface();
+        self.height() - self.surface.borrow()[col].1
+    }
+
+    // How jagged the surface is: the sum of height differences between
+    // neighbouring columns. Higher is harder to clear.
+    pub fn surface_entropy(&self) -> u32 {
+        (1..self.width())
+            .map(|col| self.column_height(col - 1).abs_diff(self.column_height(col)) as u32)
+            .sum()
+    }
+
+    // Empty cells in column `col` with a filled cell somewhere above them
+    pub fn count_holes_in_column(&self, col: usize) -> usize {
+        let mut found_top = false;
+        let mut holes = 0;
+        for row in &self.grid {
+            if row[col].is_filled() {
+                found_top = true;
+            } else if found_top {
+                holes += 1;
+            }
+        }
+        holes
+    }
+
+    // Empty cells anywhere under a filled cell in their column, over the whole
+    // board: everything that can't be reached without clearing rows first
+    pub fn count_covered_empty_cells(&self) -> usize {
+        (0..self.width()).map(|col| self.count_holes_in_column(col)).sum()
+    }
+
+    // Height of the tallest column, in rows above the floor
+    pub fn max_stack_height(&self) -> usize {
+        self.grid
+            .iter()
+            .position(|row| row.iter().any(|cell| cell.is_filled()))
+            .map_or(0, |top| self.height() - top)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gravity_compress_squeezes_out_empty_rows() {
+        let mut board = Board::new(4, 20);
+        // 15 rows with one block each, every fourth row down left empty,
+        // the bottom one included
+        for y in 0..20 {
+            if y % 4 != 3 {
+                board.fill_garbage(y % 4, y);
+            }
+        }
+
+        assert_eq!(board.gravity_compress(), 5);
+        assert_eq!(board.height(), 20);
+        assert!(board.grid[..5].iter().all(|row| row.iter().all(|cell| !cell.is_filled())));
+        assert!(board.grid[5..].iter().all(|row| row.iter().any(|cell| cell.is_filled())));
+        assert_eq!(board.gravity_compress(), 0);
+    }
+
+    #[test]
+    fn only_full_rows_with_garbage_count_as_dug() {
+        let mut board = Board::new(4, 4);
+        for x in 0..4 {
+            board.fill_garbage(x, 3);
+            board.grid[2][x] = Cell::Piece(PieceType::I);
+        }
+        board.fill_garbage(0, 1);
+        board.grid[1][1] = Cell::Piece(PieceType::O);
+        assert_eq!(board.count_dig_rows(), 1);
+
+        board.fill_garbage(2, 1);
+        board.fill_garbage(3, 1);
+        assert_eq!(board.count_dig_rows(), 2);
+        assert_eq!(board.clear_rows(), 3);
+    }
+
+    #[test]
+    fn debug_string_labels_rows_and_columns() {
+        let mut board = Board::new(3, 2);
+        board.fill_garbage(0, 1);
+        board.grid[1][2] = Cell::Piece(PieceType::T);
+        assert_eq!(board.debug_string(), "    012\n  0 ...\n  1 #.T\n");
+    }
+
+    #[test]
+    fn holes_are_the_empty_cells_under_a_column_top() {
+        let mut board = Board::new(3, 6);
+        board.fill_garbage(0, 2);
+        board.fill_garbage(0, 4);
+        board.fill_garbage(1, 5);
+        assert_eq!(board.count_holes_in_column(0), 2);
+        assert_eq!(board.count_holes_in_column(1), 0);
+        assert_eq!(board.count_holes_in_column(2), 0);
+        assert_eq!(board.count_covered_empty_cells(), 2);
+    }
+
+    #[test]
+    fn surface_cells_follow_changes_to_the_stack() {
+        let mut board = Board::new(3, 4);
+        assert!(board.column_surface_cells() == [(0, 4), (1, 4), (2, 4)]);
+
+        board.fill_garbage(1, 2);
+        board.place(&[vec![true, true]], 0, 3, PieceType::I);
+        assert!(board.column_surface_cells() == [(0, 3), (1, 2), (2, 4)]);
+
+        board.fill_garbage(2, 3);
+        board.clear_rows();
+        assert!(board.column_surface_cells() == [(0, 4), (1, 3), (2, 4)]);
+
+        // The top row, filled and then emptied again
+        board.fill_garbage(2, 0);
+        assert!(board.column_surface_cells() == [(0, 4), (1, 3), (2, 0)]);
+        board.clear_top_rows(1);
+        assert!(board.column_surface_cells() == [(0, 4), (1, 3), (2, 4)]);
+        board.place(&[vec![true], vec![true]], 0, 0, PieceType::I);
+        assert!(board.column_surface_cells() == [(0, 0), (1, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn check_size_rejects_a_board_of_another_size() {
+        let board = Board::new(10, 20);
+        assert!(board.check_size(10, 20).is_ok());
+
+        let mismatch = board.check_size(12, 20).unwrap_err();
+        assert_eq!(mismatch.expected, (12, 20));
+        assert_eq!(mismatch.found, (10, 20));
+        assert_eq!(mismatch.to_string(), "board is 10x20 but this game is 12x20");
+    }
+
+    #[test]
+    fn rows_keep_their_lock_times_through_a_collapse() {
+        let mut board = Board::new(4, 6);
+        let domino = [vec![true, true]];
+        board.place(&domino, 0, 5, PieceType::S);
+        board.stamp(&domino, 0, 5, 5.0);
+        board.place(&domino, 2, 3, PieceType::Z);
+        board.stamp(&domino, 2, 3, 2.0);
+
+        assert_eq!(board.gravity_compress(), 1);
+        assert_eq!(board.placed_at(0, 5), Some(5.0));
+        assert_eq!(board.placed_at(2, 4), Some(2.0));
+        assert_eq!(board.placed_at(2, 3), None);
+
+        board.load_field(vec![vec![Cell::Garbage, Cell::Empty, Cell::Empty, Cell::Empty]]);
+        assert_eq!(board.placed_at(0, 5), None);
+    }
+}
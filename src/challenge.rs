@@ -0,0 +1,49 @@
+use crate::board::{Board, GRID_COLS, GRID_ROWS};
+use crate::PieceType;
+use crate::PieceType::*;
+
+// Leftmost column of the 4-wide well the combo challenges are played in
+const WELL_LEFT: usize = 3;
+const WELL_WIDTH: usize = 4;
+
+// A preset board and piece sequence where every piece can clear a line, if
+// placed right, for at least `required_combo` pieces in a row
+pub struct ComboChallenge {
+    pub required_combo: u32,
+    open_col: usize,  // The one well column left empty in the bottom row
+    pub pieces: &'static [PieceType],
+}
+
+// Built-in challenges, easiest first. Each sequence has been checked to have a
+// solution using straight drops only.
+pub const COMBO_CHALLENGES: [ComboChallenge; 5] = [
+    ComboChallenge { required_combo: 4, open_col: 6, pieces: &[I, L, O, J] },
+    ComboChallenge { required_combo: 6, open_col: 3, pieces: &[J, Z, I, T, S, L] },
+    ComboChallenge { required_combo: 8, open_col: 6, pieces: &[I, L, J, S, Z, T, O, Z] },
+    ComboChallenge { required_combo: 10, open_col: 3, pieces: &[I, T, L, J, O, S, Z, T, O, L] },
+    ComboChallenge { required_combo: 12, open_col: 3, pieces: &[T, Z, I, L, S, J, O, L, O, T, I, J] },
+];
+
+impl ComboChallenge {
+    pub fn find(required_combo: u32) -> Option<&'static ComboChallenge> {
+        COMBO_CHALLENGES.iter().find(|c| c.required_combo == required_combo)
+    }
+
+    // Garbage walls either side of the well, tall enough for the whole combo,
+    // with three leftover cells at the bottom of the well to build on
+    pub fn board(&self) -> Board {
+        let mut board = Board::new(GRID_COLS, GRID_ROWS);
+        let wall_height = self.required_combo as usize + 2;
+
+        for y in GRID_ROWS - wall_height..GRID_ROWS {
+            for x in 0..GRID_COLS {
+                let in_well = (WELL_LEFT..WELL_LEFT + WELL_WIDTH).contains(&x);
+                let leftover = y == GRID_ROWS - 1 && x != self.open_col;
+                if !in_well || leftover {
+                    board.fill_garbage(x, y);
+                }
+            }
+        }
+        board
+    }
+}
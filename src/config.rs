@@ -0,0 +1,270 @@
+use crate::board::{GRID_COLS, GRID_ROWS};
+#[cfg(feature = "gamepad")]
+use crate::gamepad::PadBindings;
+use crate::input::{key_from_name, MenuKeys};
+use crate::sequence::Randomizer;
+use crate::{GameMode, PieceType, SURVIVAL_CLOCK_BONUS, SURVIVAL_CLOCK_START};
+
+// Config file in the home directory. Only the simple `key = value` subset of
+// TOML is read; `#` starts a comment and section headers are ignored.
+const CONFIG_FILE: &str = ".tetrust_config.toml";
+
+// Keys understood in the config file. Each can also be given on the command
+// line as `--key value`, with `-` for `_`, which takes precedence. The `pad_`
+// keys name controller buttons as gilrs does, e.g. `pad_hold = North`, and
+// only do anything in builds with the gamepad feature.
+const KEYS: [&str; 27] = [
+    "mode",
+    "board",
+    "block_size",
+    "ghost_alpha",
+    "piece_gravity",
+    "sound_set",
+    "ghost",
+    "record_replay",
+    "field",
+    "clock_start",
+    "clock_bonus",
+    "dig_pattern",
+    "state",
+    "spectate_port",
+    "pb_replay",
+    "randomizer",
+    "pause_key",
+    "restart_key",
+    "quit_key",
+    "utc_offset",
+    "pad_left",
+    "pad_right",
+    "pad_soft_drop",
+    "pad_hard_drop",
+    "pad_rotate",
+    "pad_hold",
+    "pad_pause",
+];
+
+// Everything decided before a game starts: the defaults, overridden by the
+// config file, overridden by the command line
+pub struct GameConfig {
+    pub mode: GameMode,
+    pub grid_dimensions: (usize, usize),
+    pub block_size: f32,
+    pub ghost_alpha: f32,
+    pub piece_gravity: [f64; 7],  // Fall interval multiplier per piece type, see GameState::piece_gravity
+    pub sound_set: Option<String>,  // Directory of a sound pack
+    pub ghost: Option<String>,  // Replay to play alongside as a training ghost
+    pub record_replay: Option<String>,  // Where to save this game's replay
+    pub field: Option<String>,  // Starting board as a fumen code, see field_code
+    pub clock_start: f64,  // Survival clock: seconds to start with
+    pub clock_bonus: f64,  // Survival clock: seconds added per cleared line
+    pub dig_pattern: Option<String>,  // File of gap columns for garbage, see dig::DigPattern
+    pub state: Option<String>,  // Saved game to resume, as written by GameState::to_json
+    pub spectate_port: Option<u16>,  // Serve game state to spectators on this TCP port
+    pub pb_replay: Option<String>,  // Replay of a personal best to show pace against
+    pub randomizer: Randomizer,  // `bag` (the default) or `classic`
+    pub menu_keys: MenuKeys,  // Pause, restart and quit, named as in input::key_from_name
+    pub utc_offset: i32,  // Hours local time is ahead of UTC, for the profile's hour chart
+    #[cfg(feature = "gamepad")]
+    pub pad_bindings: PadBindings,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::default(),
+            grid_dimensions: (GRID_COLS, GRID_ROWS),
+            block_size: 30.0,
+            ghost_alpha: 0.3,
+            piece_gravity: [1.0; 7],
+            sound_set: None,
+            ghost: None,
+            record_replay: None,
+            field: None,
+            clock_start: SURVIVAL_CLOCK_START,
+            clock_bonus: SURVIVAL_CLOCK_BONUS,
+            dig_pattern: None,
+            state: None,
+            spectate_port: None,
+            pb_replay: None,
+            randomizer: Randomizer::SevenBag,
+            menu_keys: MenuKeys::default(),
+            utc_offset: 0,
+            #[cfg(feature = "gamepad")]
+            pad_bindings: PadBindings::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+
+        let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"));
+        if let Ok(home) = home {
+            let path = std::path::Path::new(&home).join(CONFIG_FILE);
+            match std::fs::read_to_string(&path) {
+                Ok(text) => config.apply_file(&text),
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    eprintln!("Couldn't read {}: {}", path.display(), err);
+                }
+                Err(_) => {}
+            }
+        }
+
+        for key in KEYS {
+            if let Some(value) = arg_value(&format!("--{}", key.replace('_', "-"))) {
+                config.set(key, &value);
+            }
+        }
+
+        // Challenges and puzzles come with their own standard-size board, and
+        // sideways play and fractional physics are laid out for one
+        let standard_board = matches!(
+            config.mode,
+            GameMode::ComboChallenge { .. } | GameMode::Puzzle { .. } | GameMode::Sideways | GameMode::FractionalPhysics
+        );
+        if standard_board {
+            config.grid_dimensions = (GRID_COLS, GRID_ROWS);
+        }
+        config
+    }
+
+    fn apply_file(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            self.set(key.trim(), value.trim().trim_matches('"'));
+        }
+    }
+
+    // Values that don't parse leave the setting as it was
+    fn set(&mut self, key: &str, value: &str) {
+        #[cfg(feature = "gamepad")]
+        if self.pad_bindings.set(key, value) {
+            return;
+        }
+        match key {
+            "mode" => self.mode = GameMode::from_name(value),
+            "board" => {
+                if let Some(size) = parse_board_size(value) {
+                    self.grid_dimensions = size;
+                }
+            }
+            "block_size" => {
+                if let Some(size) = value.parse().ok().filter(|size| (10.0..=60.0).contains(size)) {
+                    self.block_size = size;
+                }
+            }
+            "ghost_alpha" => {
+                if let Ok(alpha) = value.parse::<f32>() {
+                    self.ghost_alpha = alpha.clamp(0.0, 1.0);
+                }
+            }
+            "piece_gravity" => apply_piece_gravity(&mut self.piece_gravity, value),
+            "sound_set" => self.sound_set = Some(value.to_string()),
+            "ghost" => self.ghost = Some(value.to_string()),
+            "record_replay" => self.record_replay = Some(value.to_string()),
+            "field" => self.field = Some(value.to_string()),
+            "dig_pattern" => self.dig_pattern = Some(value.to_string()),
+            "state" => self.state = Some(value.to_string()),
+            "pb_replay" => self.pb_replay = Some(value.to_string()),
+            "randomizer" => self.randomizer = Randomizer::from_name(value),
+            "pause_key" => {
+                if let Some(key) = key_from_name(value) {
+                    self.menu_keys.pause = key;
+                }
+            }
+            "restart_key" => {
+                if let Some(key) = key_from_name(value) {
+                    self.menu_keys.restart = key;
+                }
+            }
+            "quit_key" => {
+                if let Some(key) = key_from_name(value) {
+                    self.menu_keys.quit = key;
+                }
+            }
+            "utc_offset" => {
+                if let Some(hours) = value.parse().ok().filter(|hours| (-12..=14).contains(hours)) {
+                    self.utc_offset = hours;
+                }
+            }
+            "spectate_port" => {
+                if let Ok(port) = value.parse() {
+                    self.spectate_port = Some(port);
+                }
+            }
+            "clock_start" => {
+                if let Some(seconds) = value.parse().ok().filter(|&seconds: &f64| seconds > 0.0) {
+                    self.clock_start = seconds;
+                }
+            }
+            "clock_bonus" => {
+                if let Some(seconds) = value.parse().ok().filter(|&seconds: &f64| seconds >= 0.0) {
+                    self.clock_bonus = seconds;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Value following `name` on the command line, if given
+pub fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.position(|arg| arg == name)?;
+    args.next()
+}
+
+// `<cols>x<rows>`, within sizes the layout can cope with. Anything narrower
+// than 5 has no room to spawn the I piece.
+fn parse_board_size(size: &str) -> Option<(usize, usize)> {
+    let (cols, rows) = size.split_once('x')?;
+    Some((cols.parse().ok()?, rows.parse().ok()?))
+        .filter(|&(cols, rows)| (5..=40).contains(&cols) && (4..=200).contains(&rows))
+}
+
+// `I=2,O=0.5` or `cyan=2,yellow=0.5`: sets the multipliers for the listed
+// piece types, named by letter or color
+fn apply_piece_gravity(table: &mut [f64; 7], entries: &str) {
+    for entry in entries.split(',') {
+        let Some((name, multiplier)) = entry.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        let mut letters = name.chars();
+        let piece_type = match (letters.next(), letters.next()) {
+            (Some(letter), None) => PieceType::from_letter(letter),
+            _ => PieceType::from_color_name(name),
+        };
+        let multiplier = multiplier.trim().parse::<f64>().ok().filter(|&m| m > 0.0);
+        if let (Some(piece_type), Some(multiplier)) = (piece_type, multiplier) {
+            table[piece_type.index()] = multiplier;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piece_gravity_takes_letters_and_colors() {
+        let mut table = [1.0; 7];
+        apply_piece_gravity(&mut table, "I=2, yellow=0.5,T=0,Z=fast,junk");
+        assert_eq!(table[PieceType::I.index()], 2.0);
+        assert_eq!(table[PieceType::O.index()], 0.5);
+        assert_eq!(table[PieceType::T.index()], 1.0);
+        assert_eq!(table[PieceType::Z.index()], 1.0);
+    }
+
+    #[test]
+    fn boards_must_leave_room_to_spawn_an_i() {
+        assert_eq!(parse_board_size("4x20"), None);
+        assert_eq!(parse_board_size("5x20"), Some((5, 20)));
+        assert_eq!(parse_board_size("10x4"), Some((10, 4)));
+        assert_eq!(parse_board_size("41x20"), None);
+    }
+}
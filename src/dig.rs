@@ -0,0 +1,94 @@
+use std::fmt;
+
+// Gap columns for rising garbage, dealt in a fixed repeating order instead of
+// at random, so the same dig can be drilled again and again. The file lists
+// 0-based columns separated by spaces, commas or newlines; `#` starts a
+// comment.
+pub struct DigPattern {
+    gaps: Vec<usize>,
+    next: usize,
+}
+
+#[derive(Debug)]
+pub enum DigPatternError {
+    Io(std::io::Error),
+    Empty,
+    BadColumn(String),
+    OutOfRange { column: usize, width: usize },
+}
+
+impl fmt::Display for DigPatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DigPatternError::Io(err) => write!(f, "{}", err),
+            DigPatternError::Empty => write!(f, "no gap columns listed"),
+            DigPatternError::BadColumn(text) => write!(f, "'{}' is not a column number", text),
+            DigPatternError::OutOfRange { column, width } => {
+                write!(f, "column {} is off a board {} wide", column, width)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DigPatternError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DigPatternError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl DigPattern {
+    pub fn parse(text: &str, width: usize) -> Result<Self, DigPatternError> {
+        let mut gaps = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            for entry in line.split(|c: char| c == ',' || c.is_whitespace()).filter(|entry| !entry.is_empty()) {
+                let column: usize = entry.parse().map_err(|_| DigPatternError::BadColumn(entry.to_string()))?;
+                if column >= width {
+                    return Err(DigPatternError::OutOfRange { column, width });
+                }
+                gaps.push(column);
+            }
+        }
+        if gaps.is_empty() {
+            return Err(DigPatternError::Empty);
+        }
+        Ok(Self { gaps, next: 0 })
+    }
+
+    pub fn load(path: &str, width: usize) -> Result<Self, DigPatternError> {
+        let text = std::fs::read_to_string(path).map_err(DigPatternError::Io)?;
+        Self::parse(&text, width)
+    }
+
+    // Gap for the next garbage row, starting over after the last
+    pub fn next_gap(&mut self) -> usize {
+        let gap = self.gaps[self.next];
+        self.next = (self.next + 1) % self.gaps.len();
+        gap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_repeat_in_the_listed_order() {
+        let mut pattern = DigPattern::parse("0, 9 # edges\n4\n", 10).unwrap();
+        let gaps: Vec<usize> = (0..5).map(|_| pattern.next_gap()).collect();
+        assert_eq!(gaps, [0, 9, 4, 0, 9]);
+    }
+
+    #[test]
+    fn bad_patterns_are_rejected() {
+        assert!(matches!(DigPattern::parse("# nothing\n", 10), Err(DigPatternError::Empty)));
+        assert!(matches!(DigPattern::parse("1 x", 10), Err(DigPatternError::BadColumn(text)) if text == "x"));
+        assert!(matches!(
+            DigPattern::parse("3 10", 10),
+            Err(DigPatternError::OutOfRange { column: 10, width: 10 })
+        ));
+    }
+}
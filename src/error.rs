@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::board::SizeMismatch;
+use crate::dig::DigPatternError;
+use crate::field_code::FieldCodeError;
+use crate::json::JsonError;
+use crate::replay::ReplayLoadError;
+use crate::sound::SoundLoadError;
+
+// Any error from loading or saving the game's files, for code that deals with
+// more than one kind
+#[derive(Debug)]
+pub enum TetrusError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Sound(SoundLoadError),
+    Replay(ReplayLoadError),
+    Field(FieldCodeError),
+    Dig(DigPatternError),
+    Json(JsonError),
+    // A saved game from a different board size than the one configured
+    Size(SizeMismatch),
+}
+
+impl fmt::Display for TetrusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TetrusError::Io(err) => write!(f, "{}", err),
+            TetrusError::Image(err) => write!(f, "{}", err),
+            TetrusError::Sound(err) => write!(f, "sound set: {}", err),
+            TetrusError::Replay(err) => write!(f, "replay: {}", err),
+            TetrusError::Field(err) => write!(f, "field: {}", err),
+            TetrusError::Dig(err) => write!(f, "dig pattern: {}", err),
+            TetrusError::Json(err) => write!(f, "saved state: {}", err),
+            TetrusError::Size(err) => write!(f, "saved state: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TetrusError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TetrusError::Io(err) => Some(err),
+            TetrusError::Image(err) => Some(err),
+            TetrusError::Sound(err) => Some(err),
+            TetrusError::Replay(err) => Some(err),
+            TetrusError::Field(err) => Some(err),
+            TetrusError::Dig(err) => Some(err),
+            TetrusError::Json(err) => Some(err),
+            TetrusError::Size(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for TetrusError {
+    fn from(err: std::io::Error) -> Self {
+        TetrusError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for TetrusError {
+    fn from(err: image::ImageError) -> Self {
+        TetrusError::Image(err)
+    }
+}
+
+impl From<SoundLoadError> for TetrusError {
+    fn from(err: SoundLoadError) -> Self {
+        TetrusError::Sound(err)
+    }
+}
+
+impl From<ReplayLoadError> for TetrusError {
+    fn from(err: ReplayLoadError) -> Self {
+        TetrusError::Replay(err)
+    }
+}
+
+impl From<FieldCodeError> for TetrusError {
+    fn from(err: FieldCodeError) -> Self {
+        TetrusError::Field(err)
+    }
+}
+
+impl From<DigPatternError> for TetrusError {
+    fn from(err: DigPatternError) -> Self {
+        TetrusError::Dig(err)
+    }
+}
+
+impl From<JsonError> for TetrusError {
+    fn from(err: JsonError) -> Self {
+        TetrusError::Json(err)
+    }
+}
+
+impl From<SizeMismatch> for TetrusError {
+    fn from(err: SizeMismatch) -> Self {
+        TetrusError::Size(err)
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+
+use crate::board::Cell;
+use crate::PieceType;
+
+// Starting fields shared as fumen codes, the format of the fumen editor and
+// most community tools, e.g. `v115@bhI8KeAgH` or the whole editor URL. Only
+// the first page's field is read: pieces, comments and later pages are
+// ignored. Fumen fields are 10 wide with 23 rows; the rows from the top of
+// the stack down fill the bottom of the board.
+//
+// A v115 page starts with the field as runs of identical cells, each two
+// base64 digits (low first) holding `(block + 8) * 240 + length - 1`, top
+// row first. Blocks are 0 for empty, then I L O Z T J S and 8 for garbage;
+// on the first page they're a difference from an empty field, so the `+ 8`
+// is all that changes them. A 24th row below the floor holds rising garbage
+// and is skipped here.
+
+// Cells per fumen row, and visible rows
+const FIELD_WIDTH: usize = 10;
+const FIELD_HEIGHT: usize = 23;
+// Cells in the encoded field, the row below the floor included
+const FIELD_CELLS: usize = FIELD_WIDTH * (FIELD_HEIGHT + 1);
+const BASE64: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+// What follows the field on a page with no piece, as the editor writes it
+const EMPTY_PAGE: &str = "AgH";
+
+#[derive(Debug)]
+pub enum FieldCodeError {
+    Empty,
+    NotFumen,  // No `v115@` data in it
+    BadChar(char),
+    Truncated,  // Ends before the whole field is described
+    Corrupt,  // Runs that add up to more than the field, or unknown blocks
+    Width { cols: usize },
+    TooManyRows { found: usize, max: usize },
+}
+
+impl fmt::Display for FieldCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldCodeError::Empty => write!(f, "empty fumen code"),
+            FieldCodeError::NotFumen => write!(f, "not a v115 fumen code"),
+            FieldCodeError::BadChar(found) => write!(f, "unexpected '{}' in fumen code", found),
+            FieldCodeError::Truncated => write!(f, "fumen code ends inside the field"),
+            FieldCodeError::Corrupt => write!(f, "fumen code doesn't describe a field"),
+            FieldCodeError::Width { cols } => {
+                write!(f, "fumen fields are {} wide, the board is {}", FIELD_WIDTH, cols)
+            }
+            FieldCodeError::TooManyRows { found, max } => {
+                write!(f, "fumen field has {} rows, the board only {}", found, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FieldCodeError {}
+
+fn block_cell(block: usize) -> Option<Cell> {
+    match block {
+        0 => Some(Cell::Empty),
+        1 => Some(Cell::Piece(PieceType::I)),
+        2 => Some(Cell::Piece(PieceType::L)),
+        3 => Some(Cell::Piece(PieceType::O)),
+        4 => Some(Cell::Piece(PieceType::Z)),
+        5 => Some(Cell::Piece(PieceType::T)),
+        6 => Some(Cell::Piece(PieceType::J)),
+        7 => Some(Cell::Piece(PieceType::S)),
+        8 => Some(Cell::Garbage),
+        _ => None,
+    }
+}
+
+fn cell_block(cell: Cell) -> usize {
+    match cell {
+        Cell::Empty => 0,
+        Cell::Piece(PieceType::I) => 1,
+        Cell::Piece(PieceType::L) => 2,
+        Cell::Piece(PieceType::O) => 3,
+        Cell::Piece(PieceType::Z) => 4,
+        Cell::Piece(PieceType::T) => 5,
+        Cell::Piece(PieceType::J) => 6,
+        Cell::Piece(PieceType::S) => 7,
+        Cell::Garbage => 8,
+    }
+}
+
+// The field of `code`'s first page from the top of the stack down, for a
+// board `cols` wide and `rows` tall. Nothing at all for an empty field.
+pub fn decode(code: &str, cols: usize, rows: usize) -> Result<Vec<Vec<Cell>>, FieldCodeError> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err(FieldCodeError::Empty);
+    }
+    let start = code
+        .find("115@")
+        .filter(|&at| at > 0 && matches!(code.as_bytes()[at - 1], b'v' | b'm' | b'd'))
+        .ok_or(FieldCodeError::NotFumen)?;
+    if cols != FIELD_WIDTH {
+        return Err(FieldCodeError::Width { cols });
+    }
+
+    // Editors may break long codes up with `?`
+    let mut chars = code[start + 4..].chars().filter(|&ch| ch != '?');
+    let mut next_digit = || {
+        let ch = chars.next().ok_or(FieldCodeError::Truncated)?;
+        BASE64.find(ch).ok_or(FieldCodeError::BadChar(ch))
+    };
+    let mut cells = Vec::with_capacity(FIELD_CELLS);
+    while cells.len() < FIELD_CELLS {
+        let value = next_digit()? + next_digit()? * 64;
+        let length = value % FIELD_CELLS + 1;
+        let cell = (value / FIELD_CELLS).checked_sub(8).and_then(block_cell).ok_or(FieldCodeError::Corrupt)?;
+        if cells.len() + length > FIELD_CELLS {
+            return Err(FieldCodeError::Corrupt);
+        }
+        cells.extend(std::iter::repeat_n(cell, length));
+    }
+
+    let field: Vec<Vec<Cell>> = cells[..FIELD_WIDTH * FIELD_HEIGHT].chunks(FIELD_WIDTH).map(<[Cell]>::to_vec).collect();
+    let top = field.iter().position(|row| row.iter().any(|cell| cell.is_filled())).unwrap_or(FIELD_HEIGHT);
+    if FIELD_HEIGHT - top > rows {
+        return Err(FieldCodeError::TooManyRows { found: FIELD_HEIGHT - top, max: rows });
+    }
+    Ok(field[top..].to_vec())
+}
+
+// `grid` as a one-page fumen code, or None if it isn't 10 wide or its stack
+// is taller than a fumen field
+pub fn encode(grid: &[Vec<Cell>]) -> Option<String> {
+    if grid.first().is_none_or(|row| row.len() != FIELD_WIDTH) {
+        return None;
+    }
+    let top = grid.iter().position(|row| row.iter().any(|cell| cell.is_filled())).unwrap_or(grid.len());
+    if grid.len() - top > FIELD_HEIGHT {
+        return None;
+    }
+
+    // The stack sits on the floor of the field, with the row below empty
+    let mut cells = vec![Cell::Empty; FIELD_CELLS];
+    let offset = (FIELD_HEIGHT - (grid.len() - top)) * FIELD_WIDTH;
+    for (i, &cell) in grid[top..].iter().flatten().enumerate() {
+        cells[offset + i] = cell;
+    }
+
+    let mut code = "v115@".to_string();
+    let mut push_digit = |digit: usize| code.push(BASE64.as_bytes()[digit] as char);
+    let mut start = 0;
+    while start < FIELD_CELLS {
+        let length = cells[start..].iter().take_while(|&&cell| cell == cells[start]).count();
+        let value = (cell_block(cells[start]) + 8) * FIELD_CELLS + length - 1;
+        push_digit(value % 64);
+        push_digit(value / 64);
+        start += length;
+    }
+    // An empty field is followed by how many pages repeat it
+    if top == grid.len() {
+        code.push('A');
+    }
+    code.push_str(EMPTY_PAGE);
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_empty_field_matches_the_editor() {
+        assert_eq!(encode(&vec![vec![Cell::Empty; 10]; 20]).unwrap(), "v115@vhAAgH");
+        assert!(decode("v115@vhAAgH", 10, 20).unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_field_survives_an_encode_and_decode() {
+        // Nine garbage cells and a gap along the floor: 220 empty cells,
+        // 9 garbage, then the gap and the row below the floor
+        let code = "https://fumen.zui.jp/?v115@bhI8KeAgH";
+        let field = decode(code, 10, 20).unwrap();
+        assert_eq!(field.len(), 1);
+        assert!(field[0][..9].iter().all(|&cell| cell == Cell::Garbage));
+        assert!(field[0][9] == Cell::Empty);
+
+        let mut grid = vec![vec![Cell::Empty; 10]; 20];
+        grid[19] = field[0].clone();
+        grid[18][4] = Cell::Piece(PieceType::T);
+        grid[18][5] = Cell::Piece(PieceType::S);
+        let encoded = encode(&grid).unwrap();
+        assert!(decode(&encoded, 10, 20).unwrap() == grid[18..]);
+        assert_eq!(encode(&grid[19..]).unwrap(), "v115@bhI8KeAgH");
+    }
+
+    #[test]
+    fn boards_that_dont_fit_a_fumen_field_are_left_out() {
+        assert!(encode(&vec![vec![Cell::Empty; 8]; 20]).is_none());
+        assert!(encode(&vec![vec![Cell::Garbage; 10]; 24]).is_none());
+        assert!(encode(&vec![vec![Cell::Garbage; 10]; 23]).is_some());
+    }
+
+    #[test]
+    fn bad_fumen_codes_say_what_is_wrong() {
+        assert!(matches!(decode("", 10, 20), Err(FieldCodeError::Empty)));
+        assert!(matches!(decode("4/X3/XXX1", 10, 20), Err(FieldCodeError::NotFumen)));
+        assert!(matches!(decode("v115@bh!8KeAgH", 10, 20), Err(FieldCodeError::BadChar('!'))));
+        assert!(matches!(decode("v115@bhI8", 10, 20), Err(FieldCodeError::Truncated)));
+        assert!(matches!(decode("v115@AA", 10, 20), Err(FieldCodeError::Corrupt)));
+        assert!(matches!(decode("v115@bhI8KeAgH", 8, 20), Err(FieldCodeError::Width { cols: 8 })));
+        let tall = encode(&vec![vec![Cell::Garbage; 10]; 6]).unwrap();
+        assert!(matches!(decode(&tall, 10, 4), Err(FieldCodeError::TooManyRows { found: 6, max: 4 })));
+    }
+}
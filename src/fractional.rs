@@ -0,0 +1,492 @@
+use macroquad::prelude::*;
+use ::rand::rngs::StdRng;
+use ::rand::{thread_rng, Rng, SeedableRng};
+
+use crate::assets::Assets;
+use crate::input::{Action, KeyBindings, MenuKeys};
+use crate::pause::{MenuCommand, PauseMenu};
+use crate::render;
+use crate::scoring;
+use crate::sequence::PieceSequence;
+use crate::settings::ScoringMode;
+use crate::{GameState, PieceType, MAX_DELTA_MS, TICK};
+
+// Rows a second a piece falls, normally and with soft drop held
+const FALL_SPEED: f32 = 1.5;
+const SOFT_DROP_SPEED: f32 = 15.0;
+// Fill that trickles out of a cell into room below it, per second
+const FLOW_RATE: f32 = 3.0;
+// Seconds a piece rests on the stack before locking
+const LOCK_DELAY: f32 = 0.5;
+// Fill levels and positions this close count as the same, so fractions that
+// add up to a whole cell make a full one
+const EPSILON: f32 = 1e-4;
+// Side panel width, in blocks
+const PANEL_BLOCKS: f32 = 6.0;
+
+#[derive(Clone, Copy, Default)]
+pub struct FractionalCell {
+    pub fill_level: f32,  // 0 empty to 1 full; the fill sits at the bottom of the cell
+    pub piece_type: Option<PieceType>,  // Where the fill came from, for its color
+}
+
+impl FractionalCell {
+    fn is_full(&self) -> bool {
+        self.fill_level >= 1.0 - EPSILON
+    }
+
+    fn is_empty(&self) -> bool {
+        self.fill_level <= EPSILON
+    }
+}
+
+// Board for GameMode::FractionalPhysics. Pieces come to rest wherever they
+// touch the fill below, between rows if need be, and their blocks pour into
+// the cells they cover. Fill trickles down into any room under it, like sand.
+pub struct FractionalBoard {
+    pub grid: Vec<Vec<FractionalCell>>,
+}
+
+impl FractionalBoard {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self { grid: vec![vec![FractionalCell::default(); cols]; rows] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    // How much of the band from `top` to `top + 1` in column `x` is filled
+    // already. Rows above the board are empty.
+    fn overlap(&self, x: usize, top: f32) -> f32 {
+        let first = top.floor() as i32;
+        let mut overlap = 0.0;
+        for row in first..=first + 1 {
+            if row < 0 || row as usize >= self.height() {
+                continue;
+            }
+            let fill_top = (row + 1) as f32 - self.grid[row as usize][x].fill_level;
+            overlap += ((top + 1.0).min((row + 1) as f32) - top.max(fill_top)).max(0.0);
+        }
+        overlap
+    }
+
+    // Whether `shape` fits with its top-left at column `x` and row `y`, which
+    // can be fractional. Blocks may rest on fill but not sink into it.
+    pub fn can_move(&self, shape: &[Vec<bool>], x: i32, y: f32) -> bool {
+        blocks(shape).all(|(row, col)| {
+            let col = x + col as i32;
+            let top = y + row as f32;
+            col >= 0
+                && (col as usize) < self.width()
+                && top + 1.0 <= self.height() as f32 + EPSILON
+                && self.overlap(col as usize, top) <= EPSILON
+        })
+    }
+
+    // How far a block whose bottom edge is at `bottom` in column `x` can fall
+    // before it touches fill or the floor
+    fn room_below(&self, x: usize, bottom: f32) -> f32 {
+        let first = (bottom + EPSILON).floor().max(0.0) as usize;
+        for row in first..self.height() {
+            let cell = self.grid[row][x];
+            if !cell.is_empty() {
+                return ((row + 1) as f32 - cell.fill_level - bottom).max(0.0);
+            }
+        }
+        (self.height() as f32 - bottom).max(0.0)
+    }
+
+    // How far `shape` at (x, y) can fall before one of its blocks lands
+    pub fn drop_distance(&self, shape: &[Vec<bool>], x: i32, y: f32) -> f32 {
+        blocks(shape)
+            .map(|(row, col)| self.room_below((x + col as i32) as usize, y + row as f32 + 1.0))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    // Pours a resting piece's blocks into the cells they cover. Anything
+    // above the board is lost; returns false if some was.
+    pub fn place(&mut self, shape: &[Vec<bool>], x: i32, y: f32, piece_type: PieceType) -> bool {
+        let mut fits = true;
+        for (row, col) in blocks(shape) {
+            let col = (x + col as i32) as usize;
+            let top = y + row as f32;
+            let mut first = top.floor();
+            let mut lower = top - first;  // The part of the block in the row below `first`
+            if lower > 1.0 - EPSILON {
+                first += 1.0;
+                lower = 0.0;
+            }
+            fits &= self.pour(first as i32, col, 1.0 - lower, piece_type);
+            if lower > EPSILON {
+                fits &= self.pour(first as i32 + 1, col, lower, piece_type);
+            }
+        }
+        fits
+    }
+
+    fn pour(&mut self, row: i32, col: usize, amount: f32, piece_type: PieceType) -> bool {
+        if row < 0 {
+            return false;
+        }
+        let cell = &mut self.grid[row as usize][col];
+        cell.fill_level = (cell.fill_level + amount).min(1.0);
+        cell.piece_type = cell.piece_type.or(Some(piece_type));
+        true
+    }
+
+    // Lets fill run down into room below it for `dt` seconds, one row at
+    // most. Cells in `blocked` take no fill, so nothing pours into the
+    // falling piece.
+    pub fn settle(&mut self, dt: f32, blocked: &[(usize, usize)]) {
+        for row in (0..self.height() - 1).rev() {
+            for col in 0..self.width() {
+                let (above, below) = (self.grid[row][col], self.grid[row + 1][col]);
+                if above.is_empty() || below.is_full() || blocked.contains(&(col, row + 1)) {
+                    continue;
+                }
+                let amount = above.fill_level.min(1.0 - below.fill_level).min(FLOW_RATE * dt);
+                self.grid[row + 1][col].fill_level += amount;
+                self.grid[row + 1][col].piece_type = below.piece_type.or(above.piece_type);
+                self.grid[row][col].fill_level -= amount;
+                if self.grid[row][col].is_empty() {
+                    self.grid[row][col] = FractionalCell::default();
+                }
+            }
+        }
+    }
+
+    // Removes rows where every cell is full, dropping everything above them.
+    // Returns the number of rows removed.
+    pub fn clear_rows(&mut self) -> u32 {
+        let (width, before) = (self.width(), self.height());
+        self.grid.retain(|row| !row.iter().all(FractionalCell::is_full));
+        let cleared = before - self.grid.len();
+        for _ in 0..cleared {
+            self.grid.insert(0, vec![FractionalCell::default(); width]);
+        }
+        cleared as u32
+    }
+}
+
+// Rows and columns of the filled cells of `shape`
+fn blocks(shape: &[Vec<bool>]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    shape.iter().enumerate().flat_map(|(row, cells)| {
+        cells.iter().enumerate().filter(|&(_, &filled)| filled).map(move |(col, _)| (row, col))
+    })
+}
+
+struct FractionalPiece {
+    shape: Vec<Vec<bool>>,
+    x: i32,
+    y: f32,  // Row of the shape's top edge, fractional while it falls
+    piece_type: PieceType,
+}
+
+impl FractionalPiece {
+    fn new(piece_type: PieceType, board_width: usize) -> Self {
+        Self {
+            shape: GameState::get_piece_shape(piece_type),
+            x: board_width as i32 / 2 - 1,
+            y: 0.0,
+            piece_type,
+        }
+    }
+
+    // Board cells the piece overlaps, as (column, row)
+    fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for (row, col) in blocks(&self.shape) {
+            let top = self.y + row as f32;
+            let col = (self.x + col as i32) as usize;
+            for covered in [top.floor(), (top + 1.0 - EPSILON).floor()] {
+                if covered >= 0.0 && !cells.contains(&(col, covered as usize)) {
+                    cells.push((col, covered as usize));
+                }
+            }
+        }
+        cells
+    }
+}
+
+// One game of the fractional physics mode
+pub struct FractionalGame {
+    pub board: FractionalBoard,
+    piece: FractionalPiece,
+    sequence: PieceSequence,
+    soft_dropping: bool,
+    rested: f32,  // Seconds the piece has spent on the stack
+    pub score: u32,
+    pub lines: u32,
+    pub game_over: bool,
+}
+
+impl FractionalGame {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        let mut sequence = PieceSequence::new(StdRng::seed_from_u64(thread_rng().gen()));
+        let piece = FractionalPiece::new(sequence.next(), cols);
+        Self {
+            board: FractionalBoard::new(cols, rows),
+            piece,
+            sequence,
+            soft_dropping: false,
+            rested: 0.0,
+            score: 0,
+            lines: 0,
+            game_over: false,
+        }
+    }
+
+    fn fits(&self, shape: &[Vec<bool>], x: i32, y: f32) -> bool {
+        self.board.can_move(shape, x, y)
+    }
+
+    pub fn apply_actions(&mut self, actions: &[Action]) {
+        self.soft_dropping = actions.contains(&Action::SoftDrop);
+        for &action in actions {
+            let piece = &self.piece;
+            match action {
+                Action::MoveLeft | Action::MoveRight => {
+                    let dx = if action == Action::MoveLeft { -1 } else { 1 };
+                    if self.fits(&piece.shape, piece.x + dx, piece.y) {
+                        self.piece.x += dx;
+                    }
+                }
+                Action::Rotate => {
+                    let shape = GameState::rotate_shape(&piece.shape);
+                    if self.fits(&shape, piece.x, piece.y) {
+                        self.piece.shape = shape;
+                    }
+                }
+                Action::HardDrop => {
+                    self.piece.y += self.board.drop_distance(&piece.shape, piece.x, piece.y);
+                    self.lock_piece();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Moves the piece down, lets the fill settle and locks a piece that has
+    // rested for LOCK_DELAY
+    pub fn tick(&mut self) {
+        if self.game_over {
+            return;
+        }
+        let speed = if self.soft_dropping { SOFT_DROP_SPEED } else { FALL_SPEED };
+        let room = self.board.drop_distance(&self.piece.shape, self.piece.x, self.piece.y);
+        let step = speed * TICK as f32;
+        self.piece.y += step.min(room);
+        if room <= step {
+            self.rested += TICK as f32;
+            if self.rested >= LOCK_DELAY {
+                self.lock_piece();
+                return;
+            }
+        } else {
+            self.rested = 0.0;
+        }
+        self.board.settle(TICK as f32, &self.piece.cells());
+    }
+
+    fn lock_piece(&mut self) {
+        let piece = &self.piece;
+        if !self.board.place(&piece.shape, piece.x, piece.y, piece.piece_type) {
+            self.game_over = true;
+        }
+        let lines = self.board.clear_rows();
+        self.lines += lines;
+        self.score += scoring::line_clear_score(ScoringMode::Guideline, lines, 1);
+
+        self.piece = FractionalPiece::new(self.sequence.next(), self.board.width());
+        self.rested = 0.0;
+        if !self.fits(&self.piece.shape, self.piece.x, self.piece.y) {
+            self.game_over = true;
+        }
+    }
+
+    fn draw(&self, block_size: f32) {
+        let (cols, rows) = (self.board.width(), self.board.height());
+        for y in 0..rows + 2 {
+            for x in 0..cols + 2 {
+                if y == 0 || y == rows + 1 || x == 0 || x == cols + 1 {
+                    draw_rectangle(x as f32 * block_size, y as f32 * block_size, block_size, block_size, DARKGRAY);
+                }
+            }
+        }
+
+        // Each cell's fill drawn up from its bottom edge
+        for (y, row) in self.board.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if cell.is_empty() {
+                    continue;
+                }
+                let color = cell.piece_type.map_or(GRAY, |piece_type| piece_type.get_color());
+                let height = cell.fill_level * block_size;
+                draw_rectangle(
+                    (x + 1) as f32 * block_size,
+                    (y + 2) as f32 * block_size - height,
+                    block_size - 1.0,
+                    height - 1.0,
+                    color
+                );
+            }
+        }
+
+        if !self.game_over {
+            let piece = &self.piece;
+            for (row, col) in blocks(&piece.shape) {
+                draw_rectangle(
+                    (piece.x + col as i32 + 1) as f32 * block_size,
+                    (piece.y + row as f32 + 1.0) * block_size,
+                    block_size - 1.0,
+                    block_size - 1.0,
+                    piece.piece_type.get_color()
+                );
+            }
+        }
+
+        let x = (cols + 2) as f32 * block_size + 10.0;
+        let mut y = block_size;
+        draw_text("NEXT", x, y, 24.0, WHITE);
+        y += 10.0;
+        let next = self.sequence.peek(1)[0];
+        render::draw_mini_shape(&render::mini_piece_cells(next, next.get_color()), x, y, block_size * 0.4);
+        y += block_size * 2.0;
+        draw_text(&format!("SCORE {}", self.score), x, y, 20.0, WHITE);
+        y += 22.0;
+        draw_text(&format!("LINES {}", self.lines), x, y, 20.0, WHITE);
+    }
+}
+
+// Plays fractional physics until the player quits. It has its own board and
+// rules, so it runs apart from the main game loop, like split screen.
+pub async fn run(cols: usize, rows: usize, block_size: f32, menu_keys: MenuKeys, assets: &Assets) {
+    let mut game = FractionalGame::new(cols, rows);
+    request_new_screen_size(
+        (cols as f32 + 2.0 + PANEL_BLOCKS) * block_size,
+        (rows as f32 + 2.0) * block_size
+    );
+
+    let bindings = KeyBindings::single_player();
+    let menu_bindings = KeyBindings::menu(menu_keys);
+    let mut pause_menu = PauseMenu::new();
+    let mut accumulator = 0.0;
+    loop {
+        clear_background(BLACK);
+
+        for action in menu_bindings.poll() {
+            match pause_menu.handle(action, game.game_over) {
+                Some(MenuCommand::Restart) => {
+                    game = FractionalGame::new(cols, rows);
+                    accumulator = 0.0;
+                }
+                Some(MenuCommand::Quit) => return,
+                None => {}
+            }
+        }
+
+        if !game.game_over && !pause_menu.is_open() {
+            game.apply_actions(&bindings.poll());
+            accumulator += (get_frame_time() as f64).min(MAX_DELTA_MS / 1000.0);
+            while accumulator >= TICK && !game.game_over {
+                accumulator -= TICK;
+                game.tick();
+            }
+        }
+
+        if let Some(background) = &assets.background {
+            render::draw_background(background);
+        }
+        game.draw(block_size);
+        if game.game_over {
+            let hint = format!(
+                "{} to restart, {} to quit",
+                menu_bindings.key_name(Action::Restart),
+                menu_bindings.key_name(Action::Quit)
+            );
+            draw_text("GAME OVER", block_size * 1.5, rows as f32 * block_size / 2.0, 32.0, WHITE);
+            draw_text(&hint, block_size * 1.5, rows as f32 * block_size / 2.0 + 26.0, 18.0, LIGHTGRAY);
+        }
+        pause_menu.draw(&menu_bindings, block_size, block_size * 2.0, 0.7);
+
+        next_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const O: [[bool; 2]; 2] = [[true, true], [true, true]];
+
+    fn o_shape() -> Vec<Vec<bool>> {
+        O.iter().map(|row| row.to_vec()).collect()
+    }
+
+    #[test]
+    fn pieces_rest_on_partial_fill_between_rows() {
+        let mut board = FractionalBoard::new(4, 6);
+        board.grid[5][0].fill_level = 0.5;
+        board.grid[5][1].fill_level = 0.5;
+        let shape = o_shape();
+
+        assert!((board.drop_distance(&shape, 0, 0.0) - 3.5).abs() < EPSILON);
+        assert!(board.can_move(&shape, 0, 3.5));
+        assert!(!board.can_move(&shape, 0, 3.6));
+        // Next to the fill there's room all the way down
+        assert!(board.can_move(&shape, 2, 4.0));
+    }
+
+    #[test]
+    fn a_row_clears_only_once_every_cell_is_full() {
+        let mut board = FractionalBoard::new(2, 4);
+        // An O resting on half-full cells fills the two rows below its top
+        // and leaves the one it pokes into half full
+        board.grid[3][0].fill_level = 0.5;
+        board.grid[3][1].fill_level = 0.5;
+        assert!(board.place(&o_shape(), 0, 1.5, PieceType::O));
+        assert_eq!(board.clear_rows(), 2);
+        assert!(board.grid[3].iter().all(|cell| (cell.fill_level - 0.5).abs() < EPSILON));
+        assert!(board.grid[2].iter().all(FractionalCell::is_empty));
+
+        board.grid[3][0].fill_level = 0.99;
+        board.grid[3][1].fill_level = 1.0;
+        assert_eq!(board.clear_rows(), 0);
+    }
+
+    #[test]
+    fn fill_trickles_down_into_room_below() {
+        let mut board = FractionalBoard::new(1, 3);
+        board.grid[0][0].fill_level = 1.0;
+        for _ in 0..120 {
+            board.settle(TICK as f32, &[]);
+        }
+        assert!(board.grid[0][0].is_empty());
+        assert!(board.grid[2][0].is_full());
+
+        // Nothing pours into the falling piece
+        let mut board = FractionalBoard::new(1, 3);
+        board.grid[0][0].fill_level = 1.0;
+        board.settle(TICK as f32, &[(0, 1)]);
+        assert!(board.grid[0][0].is_full());
+    }
+
+    #[test]
+    fn a_game_plays_out_to_the_top() {
+        let mut game = FractionalGame::new(10, 20);
+        let moves = [Action::MoveLeft, Action::Rotate, Action::MoveRight, Action::SoftDrop];
+        for i in 0..20_000 {
+            if game.game_over {
+                break;
+            }
+            game.apply_actions(&[moves[i % moves.len()]]);
+            game.tick();
+        }
+        assert!(game.game_over);
+    }
+}
@@ -0,0 +1,152 @@
+use gilrs::{Button, EventType, Gilrs};
+
+use crate::input::Action;
+
+// Buttons that can be named in the config, by their gilrs names. South is the
+// bottom face button: A on Xbox pads, Cross on PlayStation ones.
+const BUTTONS: [Button; 19] = [
+    Button::South, Button::East, Button::North, Button::West, Button::C, Button::Z,
+    Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+    Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+];
+
+// Config keys that rebind the controller, and the action each one moves
+pub const PAD_KEYS: [(&str, Action); 7] = [
+    ("pad_left", Action::MoveLeft),
+    ("pad_right", Action::MoveRight),
+    ("pad_soft_drop", Action::SoftDrop),
+    ("pad_hard_drop", Action::HardDrop),
+    ("pad_rotate", Action::Rotate),
+    ("pad_hold", Action::Hold),
+    ("pad_pause", Action::Pause),
+];
+
+// The button called `name`, ignoring case
+fn button_from_name(name: &str) -> Option<Button> {
+    BUTTONS.iter().find(|button| format!("{:?}", button).eq_ignore_ascii_case(name)).copied()
+}
+
+// Controller mapping, the same for every pad: the D-pad moves, face buttons
+// turn and hold, and the shoulders drop
+#[derive(Clone)]
+pub struct PadBindings {
+    bindings: Vec<(Button, Action)>,
+}
+
+impl Default for PadBindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (Button::DPadLeft, Action::MoveLeft),
+                (Button::DPadRight, Action::MoveRight),
+                (Button::DPadDown, Action::SoftDrop),
+                (Button::LeftTrigger, Action::SoftDrop),
+                (Button::RightTrigger, Action::HardDrop),
+                (Button::South, Action::Rotate),
+                (Button::West, Action::Hold),
+                (Button::Start, Action::Pause),
+            ],
+        }
+    }
+}
+
+impl PadBindings {
+    // Applies config key `key`, putting its action on the button named
+    // `value` alone. Returns false for keys that aren't controller keys.
+    // Button names that don't parse leave the mapping as it was.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let Some(&(_, action)) = PAD_KEYS.iter().find(|(name, _)| *name == key) else {
+            return false;
+        };
+        if let Some(button) = button_from_name(value) {
+            self.bindings.retain(|&(_, bound)| bound != action);
+            self.bindings.push((button, action));
+        }
+        true
+    }
+
+    fn action_for(&self, button: Button) -> Option<Action> {
+        self.bindings.iter().find(|&&(bound, _)| bound == button).map(|&(_, action)| action)
+    }
+
+    fn buttons_for(&self, action: Action) -> impl Iterator<Item = Button> + '_ {
+        self.bindings.iter().filter(move |&&(_, bound)| bound == action).map(|&(button, _)| button)
+    }
+}
+
+// What the controllers did since the last poll
+#[derive(Default)]
+pub struct PadInput {
+    pub actions: Vec<Action>,
+    pub notices: Vec<String>,  // Pads plugged in or pulled out, for a popup
+}
+
+// Every controller, through one gilrs context. Pads can be plugged in and
+// pulled out mid-game; without a working backend the game is keyboard only.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    bindings: PadBindings,
+}
+
+impl Gamepads {
+    pub fn new(bindings: PadBindings) -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                eprintln!("Controllers unavailable: {}", err);
+                None
+            }
+        };
+        Self { gilrs, bindings }
+    }
+
+    // Presses since the last poll, in order, then SoftDrop if its button is
+    // down on any connected pad. A pad pulled out mid-press stops counting.
+    pub fn poll(&mut self) -> PadInput {
+        let mut input = PadInput::default();
+        let Some(gilrs) = &mut self.gilrs else {
+            return input;
+        };
+
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(action) = self.bindings.action_for(button).filter(|&action| action != Action::SoftDrop) {
+                        input.actions.push(action);
+                    }
+                }
+                EventType::Connected => {
+                    input.notices.push(format!("{} connected", gilrs.gamepad(event.id).name()));
+                }
+                EventType::Disconnected => input.notices.push("Controller disconnected".to_string()),
+                _ => {}
+            }
+        }
+
+        let soft_drop = gilrs
+            .gamepads()
+            .any(|(_, pad)| self.bindings.buttons_for(Action::SoftDrop).any(|button| pad.is_pressed(button)));
+        if soft_drop {
+            input.actions.push(Action::SoftDrop);
+        }
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_keys_move_an_action_to_one_button() {
+        let mut bindings = PadBindings::default();
+        assert!(bindings.set("pad_soft_drop", "dpaddown"));
+        assert!(bindings.action_for(Button::LeftTrigger).is_none());
+        assert!(bindings.action_for(Button::DPadDown) == Some(Action::SoftDrop));
+
+        assert!(bindings.set("pad_rotate", "Triangle"));
+        assert!(bindings.action_for(Button::South) == Some(Action::Rotate));
+        assert!(!bindings.set("quit_key", "Start"));
+    }
+}
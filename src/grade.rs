@@ -0,0 +1,96 @@
+use crate::stats::GameStats;
+
+// Pieces per second that earns full marks for speed
+const TARGET_PPS: f64 = 2.5;
+// Lines that earn full marks for survival: a marathon's 15 levels
+const SURVIVAL_LINES: f64 = 150.0;
+// How much each part counts towards the total, in the order of `parts`
+const WEIGHTS: [f64; 5] = [0.25, 0.2, 0.2, 0.2, 0.15];
+// Lowest total for each grade, best first; anything lower is a D
+const GRADES: [(f64, char); 4] = [(0.9, 'S'), (0.75, 'A'), (0.6, 'B'), (0.4, 'C')];
+
+// A finished game marked out of 1 on five counts, and the letter grade they
+// add up to
+pub struct ChallengeScore {
+    pub efficiency: f64,  // Lines per piece, against every placed cell being cleared
+    pub speed: f64,  // Pieces per second, against TARGET_PPS
+    pub quality: f64,  // Share of cleared lines that came from tetrises
+    pub survival: f64,  // Lines cleared, against SURVIVAL_LINES
+    pub finesse: f64,  // One less the finesse errors per piece
+}
+
+impl ChallengeScore {
+    // `cols` is the board width, which sets how many lines a piece can fill
+    pub fn new(stats: &GameStats, cols: usize) -> Self {
+        let pieces = stats.pieces_placed.max(1) as f64;
+        let lines = stats.lines_cleared as f64;
+        let quality = if stats.lines_cleared > 0 {
+            (stats.tetrises * 4) as f64 / lines
+        } else {
+            0.0
+        };
+        Self {
+            efficiency: (lines / pieces / (4.0 / cols as f64)).min(1.0),
+            speed: (stats.pps() / TARGET_PPS).min(1.0),
+            quality: quality.min(1.0),
+            survival: (lines / SURVIVAL_LINES).min(1.0),
+            finesse: if stats.pieces_placed > 0 {
+                (1.0 - stats.finesse_errors as f64 / pieces).max(0.0)
+            } else {
+                0.0
+            },
+        }
+    }
+
+    // Each part with a short label, for the results screen
+    pub fn parts(&self) -> [(&'static str, f64); 5] {
+        [
+            ("EFF", self.efficiency),
+            ("SPD", self.speed),
+            ("QUA", self.quality),
+            ("SUR", self.survival),
+            ("FIN", self.finesse),
+        ]
+    }
+
+    pub fn total(&self) -> f64 {
+        self.parts().iter().zip(WEIGHTS).map(|((_, part), weight)| part * weight).sum()
+    }
+
+    pub fn grade(&self) -> char {
+        let total = self.total();
+        GRADES.iter().find(|&&(lowest, _)| total >= lowest).map_or('D', |&(_, grade)| grade)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_game_is_a_d() {
+        let score = ChallengeScore::new(&GameStats::default(), 10);
+        assert_eq!(score.total(), 0.0);
+        assert_eq!(score.grade(), 'D');
+    }
+
+    #[test]
+    fn a_fast_clean_tetris_game_is_an_s() {
+        let mut stats = GameStats::default();
+        stats.pieces_placed = 400;
+        stats.lines_cleared = 160;
+        stats.tetrises = 40;
+        stats.elapsed = 160.0;
+        let score = ChallengeScore::new(&stats, 10);
+        assert!(score.parts().iter().all(|&(_, part)| part == 1.0));
+        assert_eq!(score.grade(), 'S');
+
+        // Slow and sloppy drags it down
+        stats.elapsed = 800.0;
+        stats.finesse_errors = 200;
+        let score = ChallengeScore::new(&stats, 10);
+        assert_eq!(score.speed, 0.2);
+        assert_eq!(score.finesse, 0.5);
+        assert_eq!(score.grade(), 'A');
+    }
+}
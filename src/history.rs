@@ -0,0 +1,147 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use macroquad::prelude::*;
+
+use crate::render;
+use crate::stats::GameStats;
+
+// One line per finished game, written by `GameStats::csv_row`
+const HISTORY_PATH: &str = "tetrust_history.csv";
+// Column of the hour of day in a history row, which is in UTC
+const HOUR_COLUMN: usize = 1;
+// Radius of the ring the hour bars grow out of, and their longest length
+const RING_RADIUS: f32 = 60.0;
+const MAX_BAR: f32 = 20.0;
+
+// Appends a finished game to the history file
+pub fn record_game(stats: &GameStats, score: u32, grade: char) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(HISTORY_PATH)?;
+    writeln!(file, "{}", stats.csv_row(score, grade))
+}
+
+// Games played in each hour of the day
+#[derive(Default)]
+pub struct HourHistogram {
+    counts: [u32; 24],
+}
+
+impl HourHistogram {
+    // Counts the hour column of every row, moved `utc_offset` hours from UTC;
+    // rows that don't parse are skipped
+    pub fn from_csv(text: &str, utc_offset: i32) -> Self {
+        let mut histogram = Self::default();
+        for line in text.lines() {
+            let hour = line.split(',').nth(HOUR_COLUMN).and_then(|hour| hour.trim().parse::<i32>().ok());
+            if let Some(hour) = hour.filter(|hour| (0..24).contains(hour)) {
+                histogram.counts[(hour + utc_offset).rem_euclid(24) as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    pub fn load(utc_offset: i32) -> Self {
+        match std::fs::read_to_string(HISTORY_PATH) {
+            Ok(text) => Self::from_csv(&text, utc_offset),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("Couldn't read {}: {}", HISTORY_PATH, err);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    // Clock face centered on (x, y) with midnight at the top. Each hour's bar
+    // grows outward in proportion to the busiest hour.
+    pub fn draw(&self, x: f32, y: f32) {
+        draw_circle_lines(x, y, RING_RADIUS, 1.0, GRAY);
+
+        let busiest = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        for (hour, &count) in self.counts.iter().enumerate() {
+            let length = count as f32 / busiest as f32 * MAX_BAR;
+            if length > 0.0 {
+                draw_arc(
+                    x,
+                    y,
+                    4,
+                    RING_RADIUS + 1.0,
+                    hour as f32 * 15.0 - 90.0,
+                    length,
+                    13.0,
+                    ORANGE
+                );
+            }
+        }
+
+        for (hour, label) in [(0, "0"), (6, "6"), (12, "12"), (18, "18")] {
+            // Middle of the hour's bar
+            let angle = (hour as f32 * 15.0 - 90.0 + 6.5).to_radians();
+            let radius = RING_RADIUS + MAX_BAR + 14.0;
+            draw_text(label, x + angle.cos() * radius - 6.0, y + angle.sin() * radius + 5.0, 16.0, LIGHTGRAY);
+        }
+    }
+}
+
+// "UTC", or the zone `utc_offset` hours from it, e.g. "UTC+2"
+fn zone_name(utc_offset: i32) -> String {
+    match utc_offset {
+        0 => "UTC".to_string(),
+        offset => format!("UTC{:+}", offset),
+    }
+}
+
+// Overlay with statistics across past games. The game is paused while it's open.
+pub struct ProfileScreen {
+    pub open: bool,
+    histogram: HourHistogram,
+    utc_offset: i32,  // Hours the chart's clock is ahead of UTC, from the config
+}
+
+impl ProfileScreen {
+    pub fn new(utc_offset: i32) -> Self {
+        Self { open: false, histogram: HourHistogram::default(), utc_offset }
+    }
+
+    // Reloads the history each time it opens, so it includes the last game
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        if self.open {
+            self.histogram = HourHistogram::load(self.utc_offset);
+        }
+    }
+
+    pub fn draw(&self, x: f32, y: f32, dim: f32) {
+        render::draw_dim_overlay(Rect::new(0.0, 0.0, screen_width(), screen_height()), dim);
+        draw_text("PROFILE", x, y, 32.0, WHITE);
+        let title = format!("Games by hour of day ({})", zone_name(self.utc_offset));
+        draw_text(&title, x, y + 30.0, 18.0, LIGHTGRAY);
+        self.histogram.draw(x + 110.0, y + 160.0);
+        draw_text("P to close", x, y + 290.0, 18.0, GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hours_move_by_the_utc_offset() {
+        let csv = "1700000000,23,100,4,10,60.0,3,B\n1700003600,0,50,1,5,30.0,2,C\nnot,a,row\n";
+        let histogram = HourHistogram::from_csv(csv, 2);
+        assert_eq!(histogram.counts[1], 1);
+        assert_eq!(histogram.counts[2], 1);
+        assert_eq!(histogram.counts.iter().sum::<u32>(), 2);
+
+        let histogram = HourHistogram::from_csv(csv, -5);
+        assert_eq!(histogram.counts[18], 1);
+        assert_eq!(histogram.counts[19], 1);
+    }
+
+    #[test]
+    fn zone_names_show_the_offset() {
+        assert_eq!(zone_name(0), "UTC");
+        assert_eq!(zone_name(2), "UTC+2");
+        assert_eq!(zone_name(-5), "UTC-5");
+    }
+}
@@ -0,0 +1,56 @@
+use crate::PieceType;
+
+// Seconds for the whole flip: half to close, half to open again
+const FLIP_DURATION: f32 = 0.15;
+
+// The hold panel turning over on a swap, drawn as the piece squeezing to a
+// line and widening again with the new one
+pub struct HoldAnimation {
+    timer: f32,
+    pub scale_x: f32,  // Width of the panel piece, 1 at rest
+    pub outgoing: Option<PieceType>,  // What the panel held before, shown until it's edge-on
+}
+
+impl HoldAnimation {
+    pub fn new(outgoing: Option<PieceType>) -> Self {
+        Self { timer: 0.0, scale_x: 1.0, outgoing }
+    }
+
+    // Moves on by `dt` seconds. Returns false once the flip is over.
+    pub fn update(&mut self, dt: f32) -> bool {
+        self.timer += dt;
+        if self.timer >= FLIP_DURATION {
+            self.scale_x = 1.0;
+            return false;
+        }
+        let half = FLIP_DURATION / 2.0;
+        self.scale_x = (self.timer - half).abs() / half;
+        true
+    }
+
+    // Whether the panel has turned far enough to show the new piece
+    pub fn swapped(&self) -> bool {
+        self.timer >= FLIP_DURATION / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_closes_swaps_and_opens_in_150ms() {
+        let mut flip = HoldAnimation::new(None);
+        assert!(flip.update(0.05));
+        assert!(!flip.swapped());
+        assert!(flip.scale_x < 1.0);
+
+        assert!(flip.update(0.025));
+        assert!(flip.swapped());
+        assert!(flip.scale_x < 0.01);
+
+        assert!(flip.update(0.05));
+        assert!(!flip.update(0.05));
+        assert_eq!(flip.scale_x, 1.0);
+    }
+}
@@ -0,0 +1,230 @@
+use macroquad::prelude::*;
+
+// Everything a player can ask the game to do. Physical input is turned into
+// these first, so the game loop doesn't care where they came from.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,  // Held rather than pressed: sent every frame the key is down
+    Rotate,
+    // Rotate, then slide to the wall in direction -1 or 1; 0 picks the nearer wall
+    RotateSlide(i32),
+    HardDrop,
+    Sacrifice,
+    Rewind,
+    Hold,  // Swap the falling piece with the held one
+    ToggleDebug,
+    DebugGarbage,
+    DebugCollapse,  // Squeeze the empty rows out of the stack
+    // Handled by the pause menu rather than the game
+    Pause,
+    Restart,
+    Quit,
+}
+
+impl Action {
+    pub fn is_menu(&self) -> bool {
+        matches!(self, Action::Pause | Action::Restart | Action::Quit)
+    }
+}
+
+// Keys for the pause menu's actions, set from the config
+#[derive(Clone, Copy)]
+pub struct MenuKeys {
+    pub pause: KeyCode,
+    pub restart: KeyCode,
+    pub quit: KeyCode,
+}
+
+impl Default for MenuKeys {
+    fn default() -> Self {
+        Self { pause: KeyCode::Escape, restart: KeyCode::N, quit: KeyCode::X }
+    }
+}
+
+// Keys that can be named in the config, by the names `key_name` shows
+const KEY_NAMES: [(&str, KeyCode); 52] = [
+    ("A", KeyCode::A), ("B", KeyCode::B), ("C", KeyCode::C), ("D", KeyCode::D),
+    ("E", KeyCode::E), ("F", KeyCode::F), ("G", KeyCode::G), ("H", KeyCode::H),
+    ("I", KeyCode::I), ("J", KeyCode::J), ("K", KeyCode::K), ("L", KeyCode::L),
+    ("M", KeyCode::M), ("N", KeyCode::N), ("O", KeyCode::O), ("P", KeyCode::P),
+    ("Q", KeyCode::Q), ("R", KeyCode::R), ("S", KeyCode::S), ("T", KeyCode::T),
+    ("U", KeyCode::U), ("V", KeyCode::V), ("W", KeyCode::W), ("X", KeyCode::X),
+    ("Y", KeyCode::Y), ("Z", KeyCode::Z),
+    ("Key0", KeyCode::Key0), ("Key1", KeyCode::Key1), ("Key2", KeyCode::Key2), ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4), ("Key5", KeyCode::Key5), ("Key6", KeyCode::Key6), ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8), ("Key9", KeyCode::Key9),
+    ("F1", KeyCode::F1), ("F2", KeyCode::F2), ("F4", KeyCode::F4), ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6), ("F7", KeyCode::F7), ("F8", KeyCode::F8), ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("Escape", KeyCode::Escape), ("Enter", KeyCode::Enter), ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete), ("Home", KeyCode::Home), ("End", KeyCode::End), ("Pause", KeyCode::Pause),
+];
+
+// The key called `name`, ignoring case. F3, F9 and F12 are left out: the game
+// already uses them.
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES.iter().find(|(key_name, _)| key_name.eq_ignore_ascii_case(name)).map(|&(_, key)| key)
+}
+
+struct Binding {
+    key: KeyCode,
+    held_with: Option<KeyCode>,  // Only fires while this key is also down
+    action: Action,
+}
+
+// Keyboard mapping for one player
+pub struct KeyBindings {
+    bindings: Vec<Binding>,
+}
+
+impl KeyBindings {
+    fn new(keys: &[(KeyCode, Action)]) -> Self {
+        let mut bindings = Self { bindings: Vec::new() };
+        for &(key, action) in keys {
+            bindings.bind(key, action);
+        }
+        bindings
+    }
+
+    pub fn single_player() -> Self {
+        let mut bindings = Self::new(&[
+            (KeyCode::Left, Action::MoveLeft),
+            (KeyCode::Right, Action::MoveRight),
+            (KeyCode::Down, Action::SoftDrop),
+            (KeyCode::R, Action::Rotate),
+            (KeyCode::Space, Action::HardDrop),
+            (KeyCode::U, Action::Rewind),
+            (KeyCode::LeftShift, Action::Hold),
+            (KeyCode::F3, Action::ToggleDebug),
+            // Only act with the debug overlay up
+            (KeyCode::G, Action::DebugGarbage),
+            (KeyCode::H, Action::DebugCollapse),
+        ]);
+        // Sacrifice needs Enter pressed while Backspace is held
+        bindings.bindings.push(Binding {
+            key: KeyCode::Enter,
+            held_with: Some(KeyCode::Backspace),
+            action: Action::Sacrifice,
+        });
+        bindings
+    }
+
+    // Single player with the board on its side: the playfield's left wall is
+    // at the bottom of the screen, so Left moves pieces up toward its right
+    // wall and Right moves them down. Down still speeds up the fall.
+    pub fn sideways() -> Self {
+        let mut bindings = Self::single_player();
+        for binding in &mut bindings.bindings {
+            binding.action = match binding.action {
+                Action::MoveLeft => Action::MoveRight,
+                Action::MoveRight => Action::MoveLeft,
+                action => action,
+            };
+        }
+        bindings
+    }
+
+    // Pause menu keys, shared by every player on the keyboard
+    pub fn menu(keys: MenuKeys) -> Self {
+        Self::new(&[
+            (keys.pause, Action::Pause),
+            (keys.restart, Action::Restart),
+            (keys.quit, Action::Quit),
+        ])
+    }
+
+    // Left side of the keyboard in split screen
+    pub fn player_one() -> Self {
+        Self::new(&[
+            (KeyCode::A, Action::MoveLeft),
+            (KeyCode::D, Action::MoveRight),
+            (KeyCode::S, Action::SoftDrop),
+            (KeyCode::W, Action::Rotate),
+            (KeyCode::Space, Action::HardDrop),
+            (KeyCode::Q, Action::Hold),
+        ])
+    }
+
+    // Right side of the keyboard in split screen
+    pub fn player_two() -> Self {
+        Self::new(&[
+            (KeyCode::Left, Action::MoveLeft),
+            (KeyCode::Right, Action::MoveRight),
+            (KeyCode::Down, Action::SoftDrop),
+            (KeyCode::Up, Action::Rotate),
+            (KeyCode::Enter, Action::HardDrop),
+            (KeyCode::RightShift, Action::Hold),
+        ])
+    }
+
+    pub fn bind(&mut self, key: KeyCode, action: Action) {
+        self.bindings.push(Binding { key, held_with: None, action });
+    }
+
+    // Keeps the rotate-and-slide macro on `key`, or unbinds it for None
+    pub fn set_rotate_slide_key(&mut self, key: Option<KeyCode>) {
+        self.bindings.retain(|binding| !matches!(binding.action, Action::RotateSlide(_)));
+        if let Some(key) = key {
+            self.bind(key, Action::RotateSlide(0));
+        }
+    }
+
+    fn key_for(&self, action: Action) -> Option<KeyCode> {
+        self.bindings.iter().find(|binding| binding.action == action).map(|binding| binding.key)
+    }
+
+    // The key bound to `action`, for on-screen hints
+    pub fn key_name(&self, action: Action) -> String {
+        self.key_for(action).map_or_else(|| "-".to_string(), |key| format!("{:?}", key))
+    }
+
+    // Actions triggered this frame, in binding order
+    pub fn poll(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for binding in &self.bindings {
+            if binding.held_with.is_some_and(|key| !is_key_down(key)) {
+                continue;
+            }
+
+            let triggered = match binding.action {
+                Action::SoftDrop => is_key_down(binding.key),
+                _ => is_key_pressed(binding.key),
+            };
+            if !triggered {
+                continue;
+            }
+
+            actions.push(match binding.action {
+                // Slide toward whichever move key is held
+                Action::RotateSlide(_) => {
+                    let held = |action| self.key_for(action).is_some_and(is_key_down);
+                    if held(Action::MoveLeft) {
+                        Action::RotateSlide(-1)
+                    } else if held(Action::MoveRight) {
+                        Action::RotateSlide(1)
+                    } else {
+                        Action::RotateSlide(0)
+                    }
+                }
+                action => action,
+            });
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_match_what_the_hints_show() {
+        for (name, key) in KEY_NAMES {
+            assert_eq!(format!("{:?}", key), name);
+            assert!(key_from_name(&name.to_lowercase()) == Some(key));
+        }
+        assert!(key_from_name("F12").is_none());
+    }
+}
@@ -1,8 +1,82 @@
+use std::collections::VecDeque;
+use std::io::Write;
+
 use macroquad::prelude::*;
+use ::rand::rngs::StdRng;
+use ::rand::seq::index::sample;
 use ::rand::thread_rng;
-use ::rand::Rng;
+use ::rand::{Rng, SeedableRng};
 
-#[derive(Clone, Copy)]
+mod ai;
+mod assets;
+mod attack;
+mod background;
+mod board;
+mod challenge;
+mod config;
+mod dig;
+mod error;
+mod field_code;
+mod fractional;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod grade;
+mod history;
+mod hold_animation;
+mod puzzle;
+mod records;
+mod replay;
+mod input;
+mod json;
+mod modifiers;
+mod pause;
+mod render;
+mod rewind;
+mod scoring;
+mod sequence;
+mod settings;
+mod share;
+mod sound;
+mod tetris_animation;
+mod spectate;
+mod srs;
+mod state_json;
+mod stats;
+mod two_player;
+
+use ai::CpuPlayer;
+use assets::AssetLoader;
+use board::{Board, Cell};
+use challenge::ComboChallenge;
+use config::GameConfig;
+use dig::DigPattern;
+use error::TetrusError;
+use grade::ChallengeScore;
+use history::ProfileScreen;
+use hold_animation::HoldAnimation;
+use input::{Action, KeyBindings};
+use modifiers::Modifiers;
+use pause::{MenuCommand, PauseMenu};
+use render::Layout;
+use puzzle::Puzzle;
+use records::{ModeRecord, Records};
+use replay::{PbPace, Replay, ReplayMove, TrainingGhost};
+use scoring::DropKind;
+use rewind::RewindBuffer;
+use sequence::{PieceSequence, Randomizer};
+use settings::{GarbageShift, HardDropLock, Settings, SettingsScreen};
+use share::ShareCard;
+use sound::{SoundEvent, SoundSet};
+use spectate::SpectatorServer;
+use stats::GameStats;
+use tetris_animation::TetrisAnimation;
+
+// Board rows shown in the playfield at once; taller boards scroll
+const VISIBLE_ROWS: usize = 20;
+// How long popup messages stay on screen, in seconds
+const POPUP_DURATION: f64 = 2.0;
+
+#[derive(Clone, Copy, PartialEq)]
 enum PieceType {
     I,
     J,
@@ -13,7 +87,99 @@ enum PieceType {
     Z,
 }
 
+// How many of a piece's four rotations look different
+#[derive(Clone, Copy, PartialEq)]
+enum Symmetry {
+    None,  // J, L, T: all four differ
+    Half,  // I, S, Z: turning twice gives the same shape
+    Full,  // O: every rotation is the same
+}
+
+impl Symmetry {
+    fn distinct_rotations(&self) -> usize {
+        match self {
+            Symmetry::None => 4,
+            Symmetry::Half => 2,
+            Symmetry::Full => 1,
+        }
+    }
+}
+
 impl PieceType {
+    const ALL: [PieceType; 7] = [
+        PieceType::I,
+        PieceType::J,
+        PieceType::L,
+        PieceType::O,
+        PieceType::S,
+        PieceType::T,
+        PieceType::Z,
+    ];
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn letter(&self) -> char {
+        match self {
+            PieceType::I => 'I',
+            PieceType::J => 'J',
+            PieceType::L => 'L',
+            PieceType::O => 'O',
+            PieceType::S => 'S',
+            PieceType::T => 'T',
+            PieceType::Z => 'Z',
+        }
+    }
+
+    // Plain name of the piece's color, for people who know pieces by color
+    fn color_name(&self) -> &'static str {
+        match self {
+            PieceType::I => "cyan",
+            PieceType::J => "blue",
+            PieceType::L => "orange",
+            PieceType::O => "yellow",
+            PieceType::S => "green",
+            PieceType::T => "purple",
+            PieceType::Z => "red",
+        }
+    }
+
+    // Inverse of `color_name`, ignoring case
+    fn from_color_name(name: &str) -> Option<PieceType> {
+        PieceType::ALL.into_iter().find(|piece_type| piece_type.color_name().eq_ignore_ascii_case(name))
+    }
+
+    // Spawn orientation as text, one `█` per cell, for logs and terminals
+    fn display_shape(&self) -> &'static str {
+        match self {
+            PieceType::I => "████",
+            PieceType::J => "█  \n███",
+            PieceType::L => "  █\n███",
+            PieceType::O => "██\n██",
+            PieceType::S => " ██\n██ ",
+            PieceType::T => " █ \n███",
+            PieceType::Z => "██ \n ██",
+        }
+    }
+
+    fn from_letter(letter: char) -> Option<PieceType> {
+        PieceType::ALL.into_iter().find(|piece_type| piece_type.letter() == letter)
+    }
+
+    // Worked out from the shapes themselves rather than listed per piece
+    fn symmetry(&self) -> Symmetry {
+        let shape = GameState::get_piece_shape(*self);
+        let quarter = GameState::rotate_shape(&shape);
+        if quarter == shape {
+            Symmetry::Full
+        } else if GameState::rotate_shape(&quarter) == shape {
+            Symmetry::Half
+        } else {
+            Symmetry::None
+        }
+    }
+
     fn get_color(&self) -> Color {
         match self {
             PieceType::I => SKYBLUE,
@@ -27,19 +193,350 @@ impl PieceType {
     }
 }
 
+#[derive(Clone)]
 struct Piece {
     shape: Vec<Vec<bool>>,
     x: i32,
     y: i32,
     piece_type: PieceType,
+    rotation: u8,  // Clockwise quarter turns from the spawn orientation, 0-3
+}
+
+impl Piece {
+    fn translate(&mut self, dx: i32, dy: i32) {
+        self.x += dx;
+        self.y += dy;
+    }
+
+    // Moves the piece only if the destination is free on `board`
+    fn try_translate(&mut self, dx: i32, dy: i32, board: &Board) -> bool {
+        if board.can_move(self, self.x + dx, self.y + dy) {
+            self.translate(dx, dy);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Result of dropping a shape onto a copy of the grid
+struct Placement {
+    shape: Vec<Vec<bool>>,
+    x: i32,
+    y: i32,
+    lines_cleared: u32,
+    holes: usize,  // Board::count_covered_empty_cells after the drop and clear
+}
+
+// Fading streak left along the columns a hard-dropped piece fell through
+struct DropTrail {
+    columns: Vec<(i32, i32, i32)>,  // Board column and the first and last row streaked
+    color: Color,
+    started_at: f64,  // Simulated time of the drop
+}
+
+// Faded copy of the current piece where it was a few frames ago
+struct TrailFrame {
+    shape: Vec<Vec<bool>>,
+    x: i32,
+    y: i32,
+    opacity: f32,
+}
+
+// What a piece lock did, as reported by GameState::apply_batch
+#[derive(Clone, Copy)]
+struct LockPieceResult {
+    score: u32,  // Points for the clear, drop points not included
+    was_tetris: bool,
+}
+
+// How long a drop trail takes to fade out, in seconds
+const DROP_TRAIL_DURATION: f64 = 0.25;
+// Motion trail: most frames kept, a new frame's opacity, and the opacity at
+// which a frame is dropped after fading at TRAIL_FADE_RATE per second
+const MOTION_TRAIL_FRAMES: usize = 5;
+const TRAIL_START_OPACITY: f32 = 0.6;
+const TRAIL_MIN_OPACITY: f32 = 0.1;
+const TRAIL_FADE_RATE: f32 = 2.5;
+
+// Rows from the top that count as "about to top out"
+const DANGER_ZONE_ROWS: usize = 4;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum GameMode {
+    #[default]
+    Marathon,
+    Casual,  // Marathon with beginner relief valves like sacrificing a piece
+    // Preset board and pieces; every piece must clear until the combo is reached
+    ComboChallenge { required_combo: u32 },
+    // Marathon against a computer opponent of level 1-10, trading garbage
+    VersusCpu { cpu_level: u8 },
+    // Casual power-up variant: every so often the board can be rewound a few seconds
+    Rewind,
+    // Split-screen local versus, trading garbage
+    TwoPlayer,
+    // Preset position and an exact set of pieces to reach a goal with
+    Puzzle { number: usize },
+    // Invisible stack and 20G gravity at once: the board only shows for the
+    // frame a piece spawns, and pieces drop straight onto the stack
+    UltraHard,
+    // Marathon under a few modifiers drawn at random from `seed`
+    Mystery { seed: u64 },
+    // Marathon with nothing shown ahead of time: every piece is a surprise
+    Hardcore,
+    // Race a countdown that each cleared line tops up. Running out of time
+    // ends the game; topping out only clears the top of the board.
+    SurvivalClock,
+    // Digging practice: garbage keeps rising on a timer, with its gaps taken
+    // from a pattern file if one is given
+    DigDrill,
+    // Marathon on a board turned on its side: pieces fall from left to right
+    Sideways,
+    // Experimental: blocks can rest between rows and pour into the cells
+    // below like sand, see fractional::FractionalBoard
+    FractionalPhysics,
+}
+
+// Which way pieces fall as seen on screen. The game itself always runs with
+// gravity down the board; only the drawing and the keys are turned.
+#[derive(Clone, Copy, PartialEq)]
+enum GravityDirection {
+    Down,
+    Right,
+}
+
+impl GameMode {
+    fn allows_sacrifice(&self) -> bool {
+        *self == GameMode::Casual
+    }
+
+    // Parses a `--mode` name, falling back to marathon
+    fn from_name(mode: &str) -> GameMode {
+        if let Some(required) = mode.strip_prefix("combo").and_then(|n| n.parse().ok()) {
+            if ComboChallenge::find(required).is_some() {
+                return GameMode::ComboChallenge { required_combo: required };
+            }
+        }
+        if let Some(number) = mode.strip_prefix("puzzle").and_then(|n| n.parse().ok()) {
+            if Puzzle::find(number).is_some() {
+                return GameMode::Puzzle { number };
+            }
+        }
+        if let Some(level) = mode.strip_prefix("cpu").and_then(|n| n.parse::<u8>().ok()) {
+            return GameMode::VersusCpu { cpu_level: level.clamp(1, 10) };
+        }
+        if let Some(seed) = mode.strip_prefix("mystery") {
+            let seed = seed.parse().unwrap_or_else(|_| thread_rng().gen());
+            return GameMode::Mystery { seed };
+        }
+        match mode {
+            "casual" => GameMode::Casual,
+            "rewind" => GameMode::Rewind,
+            "2p" => GameMode::TwoPlayer,
+            "ultra" => GameMode::UltraHard,
+            "hardcore" => GameMode::Hardcore,
+            "clock" => GameMode::SurvivalClock,
+            "dig" => GameMode::DigDrill,
+            "sideways" => GameMode::Sideways,
+            "fractional" => GameMode::FractionalPhysics,
+            _ => GameMode::Marathon,
+        }
+    }
+
+    fn gravity_direction(&self) -> GravityDirection {
+        if *self == GameMode::Sideways {
+            GravityDirection::Right
+        } else {
+            GravityDirection::Down
+        }
+    }
+
+    fn shows_preview(&self) -> bool {
+        *self != GameMode::Hardcore
+    }
+
+    // Not in hardcore, and not in puzzles, where holding would pull a piece
+    // from the bags into the puzzle's fixed sequence
+    fn allows_hold(&self) -> bool {
+        !matches!(self, GameMode::Hardcore | GameMode::Puzzle { .. })
+    }
+
+    // Modes with an opponent to trade garbage with
+    fn is_versus(&self) -> bool {
+        matches!(self, GameMode::VersusCpu { .. } | GameMode::TwoPlayer)
+    }
+
+    // Stands for the mode in the shared emoji summary
+    fn emoji(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "\u{1f3c3}",
+            GameMode::Casual => "\u{1f642}",
+            GameMode::ComboChallenge { .. } => "\u{1f517}",
+            GameMode::VersusCpu { .. } => "\u{1f916}",
+            GameMode::Rewind => "\u{23ea}",
+            GameMode::TwoPlayer => "\u{1f465}",
+            GameMode::Puzzle { .. } => "\u{1f9e9}",
+            GameMode::UltraHard => "\u{1f480}",
+            GameMode::Mystery { .. } => "\u{2753}",
+            GameMode::Hardcore => "\u{1f648}",
+            GameMode::SurvivalClock => "\u{23f1}",
+            GameMode::DigDrill => "\u{26cf}",
+            GameMode::Sideways => "\u{27a1}",
+            GameMode::FractionalPhysics => "\u{23f3}",
+        }
+    }
+
+    // Name the mode's records are saved under, the same as its `--mode` value
+    fn record_key(&self) -> String {
+        match self {
+            GameMode::Marathon => "marathon".to_string(),
+            GameMode::Casual => "casual".to_string(),
+            GameMode::ComboChallenge { required_combo } => format!("combo{}", required_combo),
+            GameMode::VersusCpu { cpu_level } => format!("cpu{}", cpu_level),
+            GameMode::Rewind => "rewind".to_string(),
+            GameMode::TwoPlayer => "2p".to_string(),
+            GameMode::Puzzle { number } => format!("puzzle{}", number),
+            GameMode::UltraHard => "ultra".to_string(),
+            // One record for all of them, since any seed is as likely
+            GameMode::Mystery { .. } => "mystery".to_string(),
+            GameMode::Hardcore => "hardcore".to_string(),
+            GameMode::SurvivalClock => "clock".to_string(),
+            GameMode::DigDrill => "dig".to_string(),
+            GameMode::Sideways => "sideways".to_string(),
+            GameMode::FractionalPhysics => "fractional".to_string(),
+        }
+    }
+
+    // The name `from_name` turns back into this exact mode, parameters and
+    // all, for saving a game to pick up later
+    fn saved_name(&self) -> String {
+        match self {
+            GameMode::Mystery { seed } => format!("mystery{}", seed),
+            mode => mode.record_key(),
+        }
+    }
 }
 
+// Points lost for discarding a piece in casual mode
+const SACRIFICE_PENALTY: u32 = 500;
+// How long the score penalty floats next to the score, in seconds
+const PENALTY_ANIM_DURATION: f64 = 1.0;
+
+// Lines to clear for each level
+const LINES_PER_LEVEL: u32 = 10;
+// Level past which gravity stops getting faster
+const MAX_GRAVITY_LEVEL: u32 = 20;
+// Seconds per row while soft dropping, unless gravity is already faster
+const SOFT_DROP_INTERVAL: f64 = 0.05;
+// Pieces between board flips under the mirror modifier
+const MIRROR_INTERVAL: u32 = 8;
+// Garbage rows the cheese modifier starts with
+const CHEESE_ROWS: u8 = 6;
+// How long mystery mode shows its rules at the start, in seconds
+const MYSTERY_INTRO: f64 = 3.0;
+// Survival clock defaults: seconds on the clock at the start and added per line
+const SURVIVAL_CLOCK_START: f64 = 120.0;
+const SURVIVAL_CLOCK_BONUS: f64 = 2.0;
+// Rows wiped from the top when a piece can't spawn in survival clock mode
+const SURVIVAL_TOPOUT_ROWS: usize = 6;
+// Dig drill: garbage rows to start with, and seconds between rises after that
+const DIG_START_ROWS: u8 = 6;
+const DIG_RISE_INTERVAL: f64 = 3.0;
+
+// Clears this close together, in seconds, speed the game up
+const SPEED_CHAIN_WINDOW: f64 = 2.0;
+const SPEED_BOOST: f32 = 1.1;
+const MAX_SPEED_MULTIPLIER: f32 = 3.0;
+// Multiplier lost per second without a clear
+const SPEED_DECAY: f32 = 0.05;
+
+// Garbage received from an opponent comes in one-hole "cheese" rows
+const INCOMING_GAP_DENSITY: f32 = 0.1;
+
+// Where failed rotations are written with settings.log_rotation_failures on
+const ROTATION_LOG_PATH: &str = "tetrust_rotation.log";
+
+// Enclosed empty areas smaller than a piece can never be filled
+const MIN_PLAYABLE_POCKET: usize = 4;
+
+// Length of one simulation step, in seconds. The game advances in whole steps
+// regardless of frame rate.
+const TICK: f64 = 1.0 / 60.0;
+// Longest frame the simulation catches up on, in milliseconds. Anything beyond
+// is dropped, so coming back from a backgrounded window or sleep doesn't run
+// enough gravity at once to slam the piece down and lock it.
+const MAX_DELTA_MS: f64 = 200.0;
+
+// Grace period after a hard drop under HardDropLock::Delayed, in seconds
+const HARD_DROP_LOCK_DELAY: f64 = 0.5;
+// Most times moving or turning a landed piece can restart its lock delay
+const MAX_LOCK_RESETS: u32 = 15;
+
 struct GameState {
-    grid: Vec<Vec<Option<PieceType>>>,
+    mode: GameMode,
+    board: Board,
     current_piece: Piece,
-    last_fall: f64,
+    sequence: PieceSequence,
+    hold: Option<PieceType>,  // Set aside by the player for later
+    hold_used: bool,  // The current piece came out of, or went into, the hold
+    last_fall: f64,  // Simulated time of the last gravity step
+    lock_deadline: Option<f64>,  // Simulated time a landed piece locks after a delayed hard drop
+    soft_drop_lock_at: Option<f64>,  // Simulated time a soft-dropped piece locks unless moved first
+    // Seconds the current piece has rested on the stack since its lock delay
+    // last restarted; it locks at settings.lock_delay
+    rested: f64,
+    lock_resets: u32,  // Times the current piece's lock delay has been restarted
+    lowest_row: i32,  // Lowest the current piece has been; getting lower restores its resets
     block_size: f32,
-    fall_speed: f64,  // Time between falls in seconds
+    soft_dropping: bool,
+    settings: Settings,
+    desperation_hint: Option<Placement>,
+    game_over: bool,
+    won: bool,  // Game ended by meeting the mode's goal
+    combo: u32,  // Consecutive pieces that cleared at least one line
+    score: u32,
+    outgoing_garbage: u32,  // Garbage sent by clears that the opponent hasn't received yet
+    // Received garbage packets waiting to rise, oldest first: rows and the
+    // simulated time they rise at. Clears cancel them before sending anything.
+    pending_garbage: VecDeque<(u32, f64)>,
+    penalty_shown_at: Option<f64>,
+    stats: GameStats,
+    popup: Option<(String, f64)>,  // Message and the time it was shown
+    ticks: u64,  // Simulation steps run so far
+    zen_timer: f64,  // Seconds since the last line clear
+    speed_multiplier: f32,  // Gravity speed-up from chaining clears, 1.0 to MAX_SPEED_MULTIPLIER
+    last_clear_at: Option<f64>,  // Simulated time of the last line clear
+    speed_decay_timer: f64,  // Seconds towards the next SPEED_DECAY step
+    rewind: Option<RewindBuffer>,  // Only kept in GameMode::Rewind
+    dig_pattern: Option<DigPattern>,  // Fixed gap columns for all garbage instead of random ones
+    last_dig_rise: f64,  // Simulated time the dig drill last sent up garbage
+    drop_trails: Vec<DropTrail>,
+    motion_trail: VecDeque<TrailFrame>,  // Newest last, at most MOTION_TRAIL_FRAMES
+    tetris_animation: Option<TetrisAnimation>,  // Playing after a tetris; the game waits for it
+    hold_animation: Option<HoldAnimation>,  // The hold panel flipping over after a swap
+    sound_events: Vec<SoundEvent>,  // Queued for the main loop to play
+    isolated_holes: bool,  // Some pocket of the stack is too small for any piece
+    show_debug: bool,
+    input_accumulator: f32,  // Real seconds toward the next input slot under a rate limit
+    queued_actions: Vec<Action>,  // Polled but held back by the input rate limit
+    fresh_spawn: bool,  // Current piece hasn't been drawn yet
+    modifiers: Modifiers,
+    // Fall interval multiplier per piece type, by PieceType::index: 2.0 falls
+    // at half speed. All 1.0 unless given on the command line.
+    piece_gravity: [f64; 7],
+    clock_left: f64,  // Seconds until a survival clock game ends
+    clock_bonus: f64,  // Seconds each cleared line adds to the survival clock
+    last_move_was_rotation: bool,  // The current piece's last successful move was a turn
+    last_rotation_state: u8,  // The current piece's rotation after that turn
+    lock_results: Option<Vec<LockPieceResult>>,  // Collected only during apply_batch
+    replay: Replay,  // Every piece locked so far
+    training_ghost: Option<TrainingGhost>,  // Recorded game played alongside this one
+    pb_pace: Option<PbPace>,  // Personal best run to pace against
+    spawn_row: Option<i32>,  // Row the current piece spawned on, until the player first moves it
+    piece_presses: u32,  // Move and rotate presses spent on the current piece
+    best: Option<ModeRecord>,  // This mode's record from before this game
+    new_record: bool,  // This game beat `best`
+    rng: StdRng,  // Seeded game RNG, so garbage can be reproduced in replays
 }
 
 impl GameState {
@@ -75,30 +572,19 @@ impl GameState {
         }
     }
 
-    fn spawn_new_piece() -> Piece {
-        let mut rng = thread_rng();
-        let piece_type = match rng.gen_range(0..7) {
-            0 => PieceType::I,
-            1 => PieceType::J,
-            2 => PieceType::L,
-            3 => PieceType::O,
-            4 => PieceType::S,
-            5 => PieceType::T,
-            _ => PieceType::Z,
-        };
-
+    fn spawn_new_piece(piece_type: PieceType, board_width: usize) -> Piece {
         Piece {
             shape: Self::get_piece_shape(piece_type),
-            x: 4,
+            x: board_width as i32 / 2 - 1,
             y: 0,
             piece_type,
+            rotation: 0,
         }
     }
 
-    fn rotate_piece(&mut self) {
-        let old_shape = self.current_piece.shape.clone();
-        let rows = old_shape.len();
-        let cols = old_shape[0].len();
+    fn rotate_shape(shape: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        let rows = shape.len();
+        let cols = shape[0].len();
         
         // Create new rotated shape
         let mut new_shape = vec![vec![false; rows]; cols];
@@ -106,185 +592,2202 @@ impl GameState {
         // Rotate 90 degrees clockwise
         for i in 0..rows {
             for j in 0..cols {
-                new_shape[j][rows - 1 - i] = old_shape[i][j];
+                new_shape[j][rows - 1 - i] = shape[i][j];
             }
         }
-        
-        // Check if rotation is valid
-        let old_shape = self.current_piece.shape.clone();
-        self.current_piece.shape = new_shape;
-        
-        if !self.can_move(self.current_piece.x, self.current_piece.y) {
-            // If rotation is invalid, revert back
-            self.current_piece.shape = old_shape;
+        new_shape
+    }
+
+    // `shape` followed by each clockwise turn of it that looks different, so
+    // searches don't try the same placement twice
+    fn all_rotations(shape: &[Vec<bool>], piece_type: PieceType) -> Vec<Vec<Vec<bool>>> {
+        let mut rotations = vec![shape.to_vec()];
+        for _ in 1..piece_type.symmetry().distinct_rotations() {
+            let next = Self::rotate_shape(rotations.last().unwrap());
+            rotations.push(next);
         }
+        rotations
     }
 
-    fn clear_rows(&mut self) {
-        let mut row = 19; // Start from bottom row
-        
-        while row > 0 {
-            if self.grid[row].iter().all(|cell| cell.is_some()) {
-                // Remove the completed row
-                for r in (1..=row).rev() {
-                    self.grid[r] = self.grid[r-1].clone();
+    // Turns the piece clockwise inside its SRS box, trying each wall kick in
+    // turn until one fits
+    fn rotate_piece(&mut self) {
+        let piece = &self.current_piece;
+        let new_shape = Self::rotate_shape(&piece.shape);
+        let rotation = (piece.rotation + 1) % 4;
+        let (from_x, from_y) = srs::box_offset(piece.piece_type, piece.rotation);
+        let (to_x, to_y) = srs::box_offset(piece.piece_type, rotation);
+        let (x, y) = (piece.x - from_x + to_x, piece.y - from_y + to_y);
+        let kick = srs::clockwise_kicks(piece.piece_type, piece.rotation)
+            .iter()
+            .find(|&&(dx, dy)| self.board.shape_fits(&new_shape, x + dx, y + dy));
+
+        let Some(&(dx, dy)) = kick else {
+            if self.settings.log_rotation_failures {
+                if let Err(err) = self.log_failed_rotation() {
+                    self.show_popup(&format!("Couldn't write rotation log: {}", err));
                 }
-                // Add new empty row at top
-                self.grid[0] = vec![None; 10];
-            } else {
-                row -= 1;
             }
+            return;
+        };
+        self.current_piece.shape = new_shape;
+        self.current_piece.x = x + dx;
+        self.current_piece.y = y + dy;
+        self.current_piece.rotation = rotation;
+        self.restart_lock_delay();
+        self.last_move_was_rotation = true;
+        self.soft_drop_lock_at = None;
+        self.last_rotation_state = self.current_piece.rotation;
+    }
+
+    // Appends the piece and board around a rotation that didn't go through to
+    // ROTATION_LOG_PATH, so the exact case can be reproduced from a bug report
+    fn log_failed_rotation(&self) -> std::io::Result<()> {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(ROTATION_LOG_PATH)?;
+        writeln!(file, "{}", self.failed_rotation_entry())
+    }
+
+    // The log entry for the current piece failing to rotate. The fumen line
+    // can be opened in a fumen editor or pasted into the config's `field` to
+    // set the board up again.
+    fn failed_rotation_entry(&self) -> String {
+        let piece = &self.current_piece;
+        format!(
+            "rotation failed: piece {} rotation {} at ({}, {})\nfumen {}\n{}\n{}",
+            piece.piece_type.letter(),
+            piece.rotation,
+            piece.x,
+            piece.y,
+            field_code::encode(&self.board.grid).as_deref().unwrap_or("-"),
+            piece.piece_type.display_shape(),
+            self.board.debug_string()
+        )
+    }
+
+    // Rotates, then slides the piece as far as it goes in direction `dx` (-1 or
+    // 1), as if the move key were held with instant auto-shift
+    fn rotate_and_slide(&mut self, dx: i32) {
+        self.rotate_piece();
+        let x = self.current_piece.x;
+        while self.current_piece.try_translate(dx, 0, &self.board) {}
+        if self.current_piece.x != x {
+            self.last_move_was_rotation = false;
+        }
+    }
+
+    fn clear_rows(&mut self) -> u32 {
+        self.board.clear_rows()
+    }
+
+    // Row the current piece would land on if hard dropped now
+    fn drop_projection(&self) -> i32 {
+        // Above the stack the landing row follows from the column tops alone;
+        // a piece tucked under an overhang has to be walked down instead
+        let piece = &self.current_piece;
+        let surface = self.board.column_surface_cells();
+        let mut landing = i32::MAX;
+        for col in 0..piece.shape[0].len() {
+            let Some(bottom) = (0..piece.shape.len()).rev().find(|&row| piece.shape[row][col]) else {
+                continue;
+            };
+            let top = surface[(piece.x + col as i32) as usize].1 as i32;
+            if piece.y + bottom as i32 >= top {
+                return self.walk_down_projection();
+            }
+            landing = landing.min(top - 1 - bottom as i32);
+        }
+        landing
+    }
+
+    fn walk_down_projection(&self) -> i32 {
+        let mut y = self.current_piece.y;
+        while self.can_move(self.current_piece.x, y + 1) {
+            y += 1;
         }
+        y
+    }
+
+    fn count_holes_in_column(&self, col: usize) -> usize {
+        self.board.count_holes_in_column(col)
     }
 
     fn can_move(&self, new_x: i32, new_y: i32) -> bool {
-        for (row_idx, row) in self.current_piece.shape.iter().enumerate() {
-            for (col_idx, &cell) in row.iter().enumerate() {
-                if cell {
-                    let grid_x = new_x + col_idx as i32;
-                    let grid_y = new_y + row_idx as i32;
-                    
-                    if grid_x < 0 || grid_x >= 10 || grid_y >= 20 {
-                        return false;
-                    }
-                    
-                    if grid_y >= 0 && self.grid[grid_y as usize][grid_x as usize].is_some() {
-                        return false;
+        self.board.can_move(&self.current_piece, new_x, new_y)
+    }
+
+    // Hard drops `shape` in column `x` from the current piece's height on a copy
+    // of the grid. Returns None if the shape doesn't fit there to begin with.
+    fn simulate_placement(&self, shape: &[Vec<bool>], x: i32) -> Option<Placement> {
+        let mut y = self.current_piece.y;
+        if !self.board.shape_fits(shape, x, y) {
+            return None;
+        }
+        while self.board.shape_fits(shape, x, y + 1) {
+            y += 1;
+        }
+
+        let mut board = self.board.clone();
+        board.place(shape, x, y, self.current_piece.piece_type);
+        let lines_cleared = board.clear_rows();
+        let holes = board.count_covered_empty_cells();
+
+        Some(Placement { shape: shape.to_vec(), x, y, lines_cleared, holes })
+    }
+
+    // Whether some drop of the current piece covers no new holes
+    fn has_clean_placement(&self) -> bool {
+        let holes = self.board.count_covered_empty_cells();
+        let piece = &self.current_piece;
+        Self::all_rotations(&piece.shape, piece.piece_type).iter().any(|shape| {
+            let width = shape[0].len() as i32;
+            (0..=(self.board.width() as i32 - width))
+                .filter_map(|x| self.simulate_placement(shape, x))
+                .any(|placement| placement.holes <= holes)
+        })
+    }
+
+    // Accessibility assist: a new piece that can't be dropped anywhere without
+    // covering a hole goes straight into the hold, if the hold is free. What
+    // comes out is played as it is, even if it fits no better.
+    fn auto_hold(&mut self) {
+        if self.settings.auto_hold && !self.game_over && !self.has_clean_placement() {
+            self.hold_piece();
+        }
+    }
+
+    // When the stack is about to top out, finds a drop of the current piece
+    // that would still clear a line
+    fn find_desperation_clear(&self) -> Option<Placement> {
+        if self.board.max_stack_height() < self.board.height().saturating_sub(DANGER_ZONE_ROWS) {
+            return None;
+        }
+
+        let piece = &self.current_piece;
+        for shape in Self::all_rotations(&piece.shape, piece.piece_type) {
+            let width = shape[0].len() as i32;
+            for x in 0..=(self.board.width() as i32 - width) {
+                if let Some(placement) = self.simulate_placement(&shape, x) {
+                    if placement.lines_cleared > 0 {
+                        return Some(placement);
                     }
                 }
             }
         }
-        true
+        None
+    }
+
+    // Most points any drop of the current piece could score by clearing lines
+    // right now, for weighing placements
+    fn projected_clear_score(&self) -> u32 {
+        let piece = &self.current_piece;
+        Self::all_rotations(&piece.shape, piece.piece_type)
+            .iter()
+            .flat_map(|shape| {
+                let width = shape[0].len() as i32;
+                (0..=(self.board.width() as i32 - width)).filter_map(|x| self.simulate_placement(shape, x))
+            })
+            .map(|placement| scoring::line_clear_score(self.settings.scoring_mode, placement.lines_cleared, self.level()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Number of gravity ticks before the current piece, starting at `starting_y`,
+    // comes to rest and would lock
+    fn ticks_until_lock(&self, starting_y: i32) -> u32 {
+        let mut y = starting_y;
+        let mut ticks = 0;
+        while self.can_move(self.current_piece.x, y + 1) {
+            y += 1;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    // The piece type if the current piece was turned into place: a T, S, Z,
+    // L or J whose last move was a rotation, with at least 3 corners of its
+    // 3x3 rotation box blocked by the stack or the walls
+    fn detect_spin(&self) -> Option<PieceType> {
+        let piece = &self.current_piece;
+        let spins = matches!(
+            piece.piece_type,
+            PieceType::T | PieceType::S | PieceType::Z | PieceType::L | PieceType::J
+        );
+        if !spins || !self.last_move_was_rotation || piece.rotation != self.last_rotation_state {
+            return None;
+        }
+
+        let (offset_x, offset_y) = srs::box_offset(piece.piece_type, piece.rotation);
+        let (box_x, box_y) = (piece.x - offset_x, piece.y - offset_y);
+        let blocked = [(0, 0), (2, 0), (0, 2), (2, 2)]
+            .iter()
+            .filter(|&&(dx, dy)| {
+                let (x, y) = (box_x + dx, box_y + dy);
+                x < 0
+                    || x >= self.board.width() as i32
+                    || y >= self.board.height() as i32
+                    || (y >= 0 && self.board.grid[y as usize][x as usize].is_filled())
+            })
+            .count();
+        (blocked >= 3).then_some(piece.piece_type)
     }
 
     fn lock_piece(&mut self) {
-        for (row_idx, row) in self.current_piece.shape.iter().enumerate() {
-            for (col_idx, &cell) in row.iter().enumerate() {
-                if cell {
-                    let grid_x = self.current_piece.x + col_idx as i32;
-                    let grid_y = self.current_piece.y + row_idx as i32;
-                    
-                    if grid_y >= 0 {
-                        self.grid[grid_y as usize][grid_x as usize] = Some(self.current_piece.piece_type);
-                    }
+        self.lock_deadline = None;
+        self.soft_drop_lock_at = None;
+        self.hold_used = false;
+        let spin = self.detect_spin();
+        let piece = &self.current_piece;
+        // Lock out: part of the piece came to rest above the board, where
+        // `place` can't keep it. Survival clock makes room instead, as for a
+        // spawn that doesn't fit.
+        let locked_out = piece.y < 0 && self.mode != GameMode::SurvivalClock;
+        self.board.place(&piece.shape, piece.x, piece.y, piece.piece_type);
+        self.board.stamp(&piece.shape, piece.x, piece.y, self.sim_time());
+        self.replay.push(ReplayMove {
+            time: self.sim_time(),
+            piece_type: piece.piece_type,
+            x: piece.x,
+            y: piece.y,
+            shape: piece.shape.clone(),
+        });
+        
+        // Clear any completed rows
+        let full_rows = if self.settings.animations { self.board.full_rows() } else { Vec::new() };
+        let dug = self.board.count_dig_rows();
+        let lines = self.clear_rows();
+        // Spin practice keeps quiet about anything but spins
+        let practice = self.settings.spin_practice;
+        if lines > 0 && self.stats.record_clear(self.current_piece.piece_type) && !practice {
+            self.show_popup("ALL 7 PIECES CLEARED!");
+        }
+        // Clears score at the level they were made on
+        let level = self.level();
+        self.stats.pieces_placed += 1;
+        self.stats.lines_cleared += lines;
+        if lines >= 4 {
+            self.stats.tetrises += 1;
+        }
+        self.stats.record_finesse(self.piece_presses, self.fewest_presses());
+        self.stats.garbage_lines_cleared += dug;
+        self.stats.record_stack_height(self.board.max_stack_height());
+        self.stats.surface_entropy = self.board.surface_entropy();
+        let clear_score = if practice && spin.is_none() {
+            0
+        } else {
+            scoring::line_clear_score(self.settings.scoring_mode, lines, level)
+        };
+        self.score += clear_score;
+        if let Some(piece_type) = spin {
+            self.score += scoring::spin_bonus(level);
+            let name = match lines {
+                0 => "",
+                1 => " SINGLE",
+                2 => " DOUBLE",
+                _ => " TRIPLE",
+            };
+            self.show_popup(&format!("{}-SPIN{}!", piece_type.letter(), name));
+        }
+        self.sound_events.push(match (spin, lines) {
+            // The big clear sound, so a spin stands out
+            (Some(_), _) if practice => SoundEvent::Clear(4),
+            (None, _) if practice => SoundEvent::Lock,
+            (_, 0) => SoundEvent::Lock,
+            _ => SoundEvent::Clear(lines),
+        });
+        let attack = attack::attack_for_clear(lines, self.settings.attack_cap);
+        let result = LockPieceResult { score: clear_score, was_tetris: lines >= 4 };
+        if result.was_tetris && self.settings.animations {
+            self.tetris_animation = Some(TetrisAnimation::new(full_rows, result.score));
+        }
+        if let Some(results) = &mut self.lock_results {
+            results.push(result);
+        }
+        self.stats.garbage_sent += attack;
+        self.outgoing_garbage += self.cancel_pending_garbage(attack);
+
+        if lines > 0 {
+            self.zen_timer = 0.0;
+            if self.mode == GameMode::SurvivalClock {
+                self.clock_left += lines as f64 * self.clock_bonus;
+            }
+            self.record_speed_clear();
+            self.combo += 1;
+            self.stats.max_combo = self.stats.max_combo.max(self.combo);
+        } else {
+            self.combo = 0;
+        }
+
+        if let GameMode::ComboChallenge { required_combo } = self.mode {
+            if self.combo >= required_combo {
+                self.won = true;
+                self.game_over = true;
+            } else if lines == 0 {
+                self.game_over = true;
+            }
+        }
+
+        if let GameMode::Puzzle { number } = self.mode {
+            if let Some(puzzle) = Puzzle::find(number) {
+                if puzzle.is_solved(&self.board) {
+                    self.won = true;
+                    self.game_over = true;
+                } else if self.sequence.override_remaining() == 0 {
+                    self.game_over = true;
                 }
             }
         }
         
-        // Clear any completed rows
-        self.clear_rows();
-        
-        // Spawn new piece
-        self.current_piece = Self::spawn_new_piece();
-    }
+        self.isolated_holes = self.smallest_pocket().is_some_and(|size| size < MIN_PLAYABLE_POCKET);
+        if locked_out {
+            self.game_over = true;
+        }
 
-    fn new() -> Self {
-        Self {
-            grid: vec![vec![None; 10]; 20],
-            current_piece: Self::spawn_new_piece(),
-            last_fall: get_time(),
-            block_size: 30.0,
-            fall_speed: 0.5, // Normal fall speed
+        // Spawn new piece
+        self.spawn_next_piece();
+        if self.modifiers.mirror && self.stats.pieces_placed.is_multiple_of(MIRROR_INTERVAL) {
+            self.mirror_board();
         }
+        self.auto_hold();
+
+        self.desperation_hint = if self.settings.desperation_hint {
+            self.find_desperation_clear()
+        } else {
+            None
+        };
     }
-}
 
-#[macroquad::main("Tetris")]
-async fn main() {
-    let mut game_state = GameState::new();
-    
-    // Calculate window size based on game grid
-    let window_width = game_state.block_size * 12.0;
-    let window_height = game_state.block_size * 22.0;
-    
-    request_new_screen_size(window_width, window_height);
+    // Number of empty cells reachable from (x, y) through other empty cells,
+    // including (x, y) itself. 0 if it's filled.
+    fn flood_fill_accessible(&self, x: usize, y: usize) -> usize {
+        let mut visited = vec![vec![false; self.board.width()]; self.board.height()];
+        self.flood_fill(x, y, &mut visited)
+    }
 
-    loop {
-        clear_background(BLACK);
+    fn flood_fill(&self, x: usize, y: usize, visited: &mut [Vec<bool>]) -> usize {
+        let mut count = 0;
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if visited[y][x] || self.board.grid[y][x].is_filled() {
+                continue;
+            }
+            visited[y][x] = true;
+            count += 1;
 
-        // Handle input
-        if is_key_pressed(KeyCode::Left) {
-            let new_x = game_state.current_piece.x - 1;
-            if game_state.can_move(new_x, game_state.current_piece.y) {
-                game_state.current_piece.x = new_x;
+            if x > 0 {
+                stack.push((x - 1, y));
+            }
+            if x + 1 < self.board.width() {
+                stack.push((x + 1, y));
+            }
+            if y > 0 {
+                stack.push((x, y - 1));
+            }
+            if y + 1 < self.board.height() {
+                stack.push((x, y + 1));
             }
         }
-        if is_key_pressed(KeyCode::Right) {
-            let new_x = game_state.current_piece.x + 1;
-            if game_state.can_move(new_x, game_state.current_piece.y) {
-                game_state.current_piece.x = new_x;
+        count
+    }
+
+    // Sizes of the separate empty areas of the board, the open space above
+    // the stack included
+    fn pockets(&self) -> Vec<usize> {
+        let mut visited = vec![vec![false; self.board.width()]; self.board.height()];
+        let mut pockets = Vec::new();
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                if !visited[y][x] && !self.board.grid[y][x].is_filled() {
+                    pockets.push(self.flood_fill(x, y, &mut visited));
+                }
             }
         }
-        if is_key_down(KeyCode::Down) {
-            game_state.fall_speed = 0.05; // Fast fall speed
-        } else {
-            game_state.fall_speed = 0.5; // Normal fall speed
+        pockets
+    }
+
+    fn smallest_pocket(&self) -> Option<usize> {
+        self.pockets().into_iter().min()
+    }
+
+    // Switches to `mode` and sets up its starting board and pieces
+    fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+
+        if mode == GameMode::Rewind {
+            self.rewind = Some(RewindBuffer::default());
         }
-        if is_key_pressed(KeyCode::R) {
-            game_state.rotate_piece();
+
+        self.modifiers = match mode {
+            GameMode::UltraHard => Modifiers { invisible: true, twenty_g: true, ..Modifiers::default() },
+            GameMode::Mystery { seed } => Modifiers::roll(seed),
+            _ => Modifiers::default(),
+        };
+        if self.modifiers.cheese {
+            self.add_random_garbage(CHEESE_ROWS, INCOMING_GAP_DENSITY);
         }
-        if is_key_pressed(KeyCode::Escape) {
-            break;
+        if mode == GameMode::DigDrill {
+            self.add_random_garbage(DIG_START_ROWS, INCOMING_GAP_DENSITY);
         }
 
-        // Handle falling
-        let current_time = get_time();
-        if current_time - game_state.last_fall >= game_state.fall_speed {
-            let new_y = game_state.current_piece.y + 1;
-            if game_state.can_move(game_state.current_piece.x, new_y) {
-                game_state.current_piece.y = new_y;
-            } else {
-                game_state.lock_piece();
-            }
-            game_state.last_fall = current_time;
-        }
-
-        // Draw border
-        let border_color = DARKGRAY;
-        for y in 0..22 {
-            for x in 0..12 {
-                if y == 0 || y == 21 || x == 0 || x == 11 {
-                    draw_rectangle(
-                        x as f32 * game_state.block_size,
-                        y as f32 * game_state.block_size,
-                        game_state.block_size,
-                        game_state.block_size,
-                        border_color
-                    );
-                }
+        if let GameMode::ComboChallenge { required_combo } = mode {
+            if let Some(challenge) = ComboChallenge::find(required_combo) {
+                self.board = challenge.board();
+                self.import_piece_sequence(challenge.pieces);
             }
         }
 
-        // Draw grid
-        for y in 0..20 {
-            for x in 0..10 {
-                if let Some(piece_type) = game_state.grid[y][x] {
-                    draw_rectangle(
-                        (x as f32 + 1.0) * game_state.block_size,
-                        (y as f32 + 1.0) * game_state.block_size,
-                        game_state.block_size - 1.0,
-                        game_state.block_size - 1.0,
-                        piece_type.get_color()
-                    );
-                }
+        if let GameMode::Puzzle { number } = mode {
+            if let Some(puzzle) = Puzzle::find(number) {
+                self.board = puzzle.board();
+                self.import_piece_sequence(puzzle.pieces);
+                self.sequence.freeze();
             }
         }
+    }
 
-        // Draw current piece
-        for (dy, row) in game_state.current_piece.shape.iter().enumerate() {
-            for (dx, &cell) in row.iter().enumerate() {
-                if cell {
-                    draw_rectangle(
-                        ((game_state.current_piece.x + dx as i32 + 1) as f32) * game_state.block_size,
-                        ((game_state.current_piece.y + dy as i32 + 1) as f32) * game_state.block_size,
-                        game_state.block_size - 1.0,
-                        game_state.block_size - 1.0,
-                        game_state.current_piece.piece_type.get_color()
-                    );
-                }
-            }
+    // Deals exactly `seq`, starting with the falling piece, then carries on
+    // with the normal bags
+    fn import_piece_sequence(&mut self, seq: &[PieceType]) {
+        self.sequence.set_override(seq);
+        self.spawn_next_piece();
+    }
+
+    fn spawn_next_piece(&mut self) {
+        let piece_type = self.sequence.next();
+        self.spawn_piece(piece_type);
+    }
+
+    // Puts the falling piece in the hold and brings back the one held before,
+    // or the next piece if the hold was empty. Once per piece: what comes out
+    // can't go straight back in.
+    fn hold_piece(&mut self) -> bool {
+        if self.hold_used || !self.mode.allows_hold() {
+            return false;
+        }
+        self.lock_deadline = None;
+        self.soft_drop_lock_at = None;
+        if self.settings.animations {
+            self.hold_animation = Some(HoldAnimation::new(self.hold));
+        }
+        match self.hold.replace(self.current_piece.piece_type) {
+            Some(held) => self.spawn_piece(held),
+            None => self.spawn_next_piece(),
         }
+        self.hold_used = true;
+        true
+    }
 
-        next_frame().await
+    fn spawn_piece(&mut self, piece_type: PieceType) {
+        self.current_piece = Self::spawn_new_piece(piece_type, self.board.width());
+        self.fresh_spawn = true;
+        self.spawn_row = Some(self.current_piece.y);
+        self.piece_presses = 0;
+        self.reset_lock_delay();
+        self.last_move_was_rotation = false;
+        if self.check_game_over() && self.mode == GameMode::SurvivalClock {
+            // Only the clock ends this mode, so make room instead
+            self.board.clear_top_rows(SURVIVAL_TOPOUT_ROWS);
+        }
+        if self.check_game_over() {
+            self.game_over = true;
+        }
+    }
+
+    // Casual-mode relief valve: throws the falling piece away for a score
+    // penalty and spawns the next one. Returns false where it isn't allowed.
+    fn sacrifice_piece(&mut self) -> bool {
+        if !self.mode.allows_sacrifice() {
+            return false;
+        }
+
+        self.score = self.score.saturating_sub(SACRIFICE_PENALTY);
+        self.stats.sacrifices_used += 1;
+        self.penalty_shown_at = Some(self.sim_time());
+        // Whatever the thrown-away piece had pending goes with it, as on a lock
+        self.lock_deadline = None;
+        self.soft_drop_lock_at = None;
+        self.hold_used = false;
+        self.spawn_next_piece();
+        true
+    }
+
+    // Presses that would have taken the current piece from spawn to its
+    // rotation and column: one per clockwise turn, one per column its SRS
+    // box moved
+    fn fewest_presses(&self) -> u32 {
+        let piece = &self.current_piece;
+        let spawn_x = Self::spawn_new_piece(piece.piece_type, self.board.width()).x;
+        let box_x = piece.x - srs::box_offset(piece.piece_type, piece.rotation).0;
+        piece.rotation as u32 + (box_x - spawn_x).unsigned_abs()
+    }
+
+    // True if the freshly spawned piece already overlaps the stack. Pure, so
+    // the AI can use it to spot terminal boards.
+    fn check_game_over(&self) -> bool {
+        !self.can_move(self.current_piece.x, self.current_piece.y)
+    }
+
+    // Clears in quick succession multiply gravity, feeding back into faster play
+    fn record_speed_clear(&mut self) {
+        let now = self.sim_time();
+        if self.last_clear_at.is_some_and(|last| now - last <= SPEED_CHAIN_WINDOW) {
+            self.speed_multiplier = (self.speed_multiplier * SPEED_BOOST).min(MAX_SPEED_MULTIPLIER);
+        }
+        self.last_clear_at = Some(now);
+        self.speed_decay_timer = 0.0;
+    }
+
+    // Seconds per row of gravity at the current level, on the guideline
+    // curve: (0.8 - (level - 1) * 0.007) ^ (level - 1). It flattens out at
+    // MAX_GRAVITY_LEVEL, where a row takes under a millisecond.
+    fn level_fall_interval(&self) -> f64 {
+        let steps = (self.level().min(MAX_GRAVITY_LEVEL) - 1) as f64;
+        (0.8 - steps * 0.007).powf(steps)
+    }
+
+    // Time between falls with the speed multiplier applied, never below
+    // `settings.min_fall_interval`.
+    //
+    // The floor is off by default, so the level curve plays out in full. Set,
+    // it overrides the curve from the level whose interval drops below it,
+    // e.g. 20ms from about level 13. It only bounds how fast a piece travels;
+    // locking is still decided separately once it's grounded, so a lock delay
+    // always starts from a piece the player has had time to see land.
+    fn effective_fall_speed(&self) -> f64 {
+        let gravity = self.level_fall_interval();
+        let base = if self.soft_dropping { gravity.min(SOFT_DROP_INTERVAL) } else { gravity };
+        let mut fall_speed = base * self.piece_gravity[self.current_piece.piece_type.index()];
+        if self.modifiers.fast_gravity {
+            fall_speed /= 2.0;
+        }
+        (fall_speed / self.speed_multiplier as f64).max(self.settings.min_fall_interval)
+    }
+
+    // Upcoming pieces the player gets to see: the NEXT queue, cut to the
+    // setting, and nothing at all in hardcore
+    fn visible_preview(&self) -> Vec<PieceType> {
+        if self.mode.shows_preview() {
+            self.sequence.peek(self.settings.preview_count)
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn lines_to_next_level(&self) -> u32 {
+        LINES_PER_LEVEL - self.stats.lines_cleared % LINES_PER_LEVEL
     }
-}
\ No newline at end of file
+
+    // Starts at 1 and goes up every LINES_PER_LEVEL lines
+    fn level(&self) -> u32 {
+        self.stats.lines_cleared / LINES_PER_LEVEL + 1
+    }
+
+    // Seconds of simulated play, counted in whole ticks
+    fn sim_time(&self) -> f64 {
+        self.ticks as f64 * TICK
+    }
+
+    // Advances the simulation by one TICK, applying gravity and locking
+    fn tick(&mut self) {
+        self.ticks += 1;
+        let now = self.sim_time();
+        self.stats.elapsed = now;
+        self.zen_timer += TICK;
+        if self.mode == GameMode::SurvivalClock {
+            self.clock_left = (self.clock_left - TICK).max(0.0);
+            if self.clock_left == 0.0 {
+                self.game_over = true;
+                return;
+            }
+        }
+        self.drop_trails.retain(|trail| now - trail.started_at < DROP_TRAIL_DURATION);
+        self.rise_pending_garbage(now);
+        if self.mode == GameMode::DigDrill && now - self.last_dig_rise >= DIG_RISE_INTERVAL {
+            self.receive_garbage(1);
+            self.last_dig_rise = now;
+        }
+
+        // 20G: the piece never hangs in the air, only the lock timing is left
+        if self.modifiers.twenty_g {
+            let y = self.current_piece.y;
+            while self.current_piece.try_translate(0, 1, &self.board) {}
+            if self.current_piece.y != y {
+                self.last_move_was_rotation = false;
+            }
+        }
+        self.speed_decay_timer += TICK;
+        if self.speed_decay_timer >= 1.0 {
+            self.speed_decay_timer -= 1.0;
+            self.speed_multiplier = (self.speed_multiplier - SPEED_DECAY).max(1.0);
+        }
+
+        if let Some(ghost) = &mut self.training_ghost {
+            ghost.advance(now);
+        }
+        if let Some(pace) = &mut self.pb_pace {
+            pace.update(self.stats.lines_cleared, now);
+        }
+
+        if let Some(mut rewind) = self.rewind.take() {
+            rewind.record(self, now);
+            self.rewind = Some(rewind);
+        }
+
+        // With delays off, a grounded piece locks right away instead of waiting
+        // out the rest of the gravity interval, and the next one is live at once
+        if self.settings.zero_delays && !self.can_move(self.current_piece.x, self.current_piece.y + 1) {
+            self.lock_piece();
+            self.last_fall = now;
+            return;
+        }
+
+        if let Some(deadline) = self.lock_deadline {
+            if now >= deadline {
+                // If the piece was slid off its ledge it just keeps falling
+                self.lock_deadline = None;
+                if !self.can_move(self.current_piece.x, self.current_piece.y + 1) {
+                    self.lock_piece();
+                    self.last_fall = now;
+                    return;
+                }
+            }
+        }
+
+        if let Some(lock_at) = self.soft_drop_lock_at {
+            if now >= lock_at {
+                self.soft_drop_lock_at = None;
+                if !self.can_move(self.current_piece.x, self.current_piece.y + 1) {
+                    self.lock_piece();
+                    self.last_fall = now;
+                    return;
+                }
+            }
+        }
+
+        // A new lowest row gives the piece a fresh lock delay and resets
+        if self.current_piece.y > self.lowest_row {
+            self.reset_lock_delay();
+        }
+
+        // A landed piece rests for the lock delay, unless one of the locks
+        // above is already pending. Time in the air doesn't count, but isn't
+        // given back either, so kicking up off the stack can't stall forever.
+        let pending = self.lock_deadline.is_some() || self.soft_drop_lock_at.is_some();
+        if self.settings.lock_delay > 0.0 && !pending && !self.can_move(self.current_piece.x, self.current_piece.y + 1) {
+            self.rested += TICK;
+            if self.rested >= self.settings.lock_delay {
+                self.lock_piece();
+                self.last_fall = now;
+                return;
+            }
+        }
+
+        // Every row that has come due since the last fall drops this tick, so
+        // intervals shorter than a tick still speed the piece up, to 20G at the
+        // top of the curve. Leftover time carries over to the next row.
+        let interval = self.effective_fall_speed();
+        let due = ((now - self.last_fall) / interval).min(self.board.height() as f64) as u32;
+        if due > 0 {
+            let mut fell = 0;
+            while fell < due && self.current_piece.try_translate(0, 1, &self.board) {
+                fell += 1;
+            }
+            if fell > 0 {
+                self.last_move_was_rotation = false;
+                if self.soft_dropping {
+                    self.score += scoring::drop_score(self.settings.scoring_mode, DropKind::Soft, fell);
+                }
+            }
+
+            if fell == due {
+                self.last_fall += due as f64 * interval;
+            } else {
+                // Landed. A pending hard-drop lock takes over from gravity locking.
+                if self.soft_dropping && self.settings.soft_drop_grace > 0.0 {
+                    // Give a fumbled soft drop a moment to be taken back
+                    if self.soft_drop_lock_at.is_none() {
+                        self.soft_drop_lock_at = Some(now + self.settings.soft_drop_grace);
+                    }
+                } else if self.lock_deadline.is_none() && self.settings.lock_delay <= 0.0 {
+                    self.lock_piece();
+                }
+                self.last_fall = now;
+            }
+        }
+    }
+
+    // A move or turn once the piece has rested gives it the full lock delay
+    // again, up to MAX_LOCK_RESETS times until it reaches a new lowest row
+    fn restart_lock_delay(&mut self) {
+        if self.rested > 0.0 && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_resets += 1;
+            self.rested = 0.0;
+        }
+    }
+
+    // A whole lock delay and all its resets, counted from where the piece is
+    fn reset_lock_delay(&mut self) {
+        self.rested = 0.0;
+        self.lock_resets = 0;
+        self.lowest_row = self.current_piece.y;
+    }
+
+    // Drops the piece straight to the bottom, then locks it according to
+    // `settings.hard_drop_lock`
+    fn hard_drop(&mut self) {
+        let now = self.sim_time();
+        let start_y = self.current_piece.y;
+        while self.current_piece.try_translate(0, 1, &self.board) {}
+        let cells = (self.current_piece.y - start_y) as u32;
+        self.score += scoring::drop_score(self.settings.scoring_mode, DropKind::Hard, cells);
+        if cells > 0 {
+            self.last_move_was_rotation = false;
+        }
+
+        if self.settings.animations && self.current_piece.y > start_y {
+            self.add_drop_trail(start_y, now);
+        }
+
+        match self.settings.hard_drop_lock {
+            HardDropLock::Instant => {
+                self.lock_piece();
+                self.last_fall = now;
+            }
+            HardDropLock::Delayed => {
+                self.lock_deadline = Some(now + HARD_DROP_LOCK_DELAY);
+            }
+        }
+    }
+
+    // Streaks each column of the current piece from where its top cell was at
+    // `start_y` down to where it landed
+    fn add_drop_trail(&mut self, start_y: i32, now: f64) {
+        let piece = &self.current_piece;
+        let columns = (0..piece.shape[0].len())
+            .filter_map(|dx| {
+                let top = piece.shape.iter().position(|row| row[dx])? as i32;
+                Some((piece.x + dx as i32, start_y + top, piece.y + top - 1))
+            })
+            .collect();
+        self.drop_trails.push(DropTrail { columns, color: piece.piece_type.get_color(), started_at: now });
+    }
+
+    // Plays the tetris animation on by `dt` real seconds
+    fn update_tetris_animation(&mut self, dt: f32) {
+        if let Some(animation) = &mut self.tetris_animation {
+            if !animation.update(dt) {
+                self.tetris_animation = None;
+            }
+        }
+    }
+
+    // Turns the hold panel on by `dt` real seconds
+    fn update_hold_animation(&mut self, dt: f32) {
+        if let Some(animation) = &mut self.hold_animation {
+            if !animation.update(dt) {
+                self.hold_animation = None;
+            }
+        }
+    }
+
+    // Fades the motion trail by `dt` real seconds and, if the piece moved
+    // since the last frame, leaves a copy where it is now. A new piece starts
+    // a new trail.
+    fn update_motion_trail(&mut self, dt: f32) {
+        if !self.settings.motion_trail || !self.settings.animations || self.fresh_spawn {
+            self.motion_trail.clear();
+            return;
+        }
+        for frame in self.motion_trail.iter_mut() {
+            frame.opacity -= dt * TRAIL_FADE_RATE;
+        }
+        self.motion_trail.retain(|frame| frame.opacity > TRAIL_MIN_OPACITY);
+
+        let piece = &self.current_piece;
+        let moved = self.motion_trail.back().is_none_or(|last| {
+            last.x != piece.x || last.y != piece.y || last.shape != piece.shape
+        });
+        if moved {
+            if self.motion_trail.len() == MOTION_TRAIL_FRAMES {
+                self.motion_trail.pop_front();
+            }
+            self.motion_trail.push_back(TrailFrame {
+                shape: piece.shape.clone(),
+                x: piece.x,
+                y: piece.y,
+                opacity: TRAIL_START_OPACITY,
+            });
+        }
+    }
+
+    // Holds this frame's actions until the input rate limit lets them through.
+    // A held soft drop only counts from the latest frame.
+    fn queue_actions(&mut self, actions: Vec<Action>) {
+        self.queued_actions.retain(|&action| action != Action::SoftDrop);
+        self.queued_actions.extend(actions);
+    }
+
+    // Whether queued input may be processed this frame, at most `max_input_hz`
+    // times a second however high the frame rate; 0 means no limit. Stops a
+    // fast monitor from turning into faster input.
+    fn input_fps_limit(&mut self, max_input_hz: f32) -> bool {
+        if max_input_hz <= 0.0 {
+            self.input_accumulator = 0.0;
+            return true;
+        }
+        let period = 1.0 / max_input_hz;
+        self.input_accumulator += get_frame_time();
+        if self.input_accumulator < period {
+            return false;
+        }
+        // A long frame earns one slot, not a backlog of them
+        self.input_accumulator = (self.input_accumulator - period).min(period);
+        true
+    }
+
+    // Carries out this frame's actions. Soft drop lasts only as long as it
+    // keeps being sent.
+    fn apply_actions(&mut self, actions: &[Action]) {
+        self.soft_dropping = actions.contains(&Action::SoftDrop);
+
+        for &action in actions {
+            let places_piece = matches!(
+                action,
+                Action::MoveLeft | Action::MoveRight | Action::Rotate | Action::RotateSlide(_) | Action::HardDrop
+            );
+            if places_piece {
+                if action != Action::HardDrop {
+                    self.piece_presses += 1;
+                }
+                if let Some(row) = self.spawn_row.take() {
+                    self.stats.record_first_input(self.current_piece.y == row);
+                }
+            }
+
+            match action {
+                Action::MoveLeft | Action::MoveRight => {
+                    let dx = if action == Action::MoveLeft { -1 } else { 1 };
+                    if self.current_piece.try_translate(dx, 0, &self.board) {
+                        self.restart_lock_delay();
+                        self.last_move_was_rotation = false;
+                        self.soft_drop_lock_at = None;
+                        self.sound_events.push(SoundEvent::Move);
+                    }
+                }
+                Action::SoftDrop => {}
+                Action::Rotate => {
+                    let rotation = self.current_piece.rotation;
+                    self.rotate_piece();
+                    if self.current_piece.rotation != rotation {
+                        self.sound_events.push(SoundEvent::Rotate);
+                    }
+                }
+                Action::RotateSlide(dx) => {
+                    let toward_nearer_wall = if self.current_piece.x * 2 < self.board.width() as i32 { -1 } else { 1 };
+                    self.rotate_and_slide(if dx == 0 { toward_nearer_wall } else { dx });
+                }
+                Action::HardDrop => self.hard_drop(),
+                Action::Sacrifice => {
+                    self.sacrifice_piece();
+                }
+                Action::Rewind => {
+                    self.use_rewind();
+                }
+                Action::Hold => {
+                    self.hold_piece();
+                }
+                Action::ToggleDebug => self.show_debug = !self.show_debug,
+                // Debug tools only work with the overlay up (F3), so they
+                // can't be used by accident in a scored game
+                Action::DebugGarbage if self.show_debug => self.add_random_garbage(1, 0.1),
+                Action::DebugCollapse if self.show_debug => {
+                    self.board.gravity_compress();
+                    self.push_out_of_stack();
+                }
+                Action::DebugGarbage | Action::DebugCollapse => {}
+                Action::Pause | Action::Restart | Action::Quit => {}
+            }
+        }
+    }
+
+    // Runs the game up to each action's simulated time and applies it there,
+    // in place. Actions timed in the past apply straight away. Returns every
+    // lock on the way, in order.
+    fn apply_batch(&mut self, actions: Vec<(f64, Action)>) -> Vec<LockPieceResult> {
+        self.lock_results = Some(Vec::new());
+        for (at, action) in actions {
+            while self.sim_time() < at && !self.game_over {
+                self.tick();
+            }
+            if self.game_over {
+                break;
+            }
+            self.apply_actions(&[action]);
+        }
+        self.lock_results.take().unwrap_or_default()
+    }
+
+    // Rewind-mode power-up: puts the board back a few seconds. Returns false
+    // in other modes or while it's cooling down.
+    fn use_rewind(&mut self) -> bool {
+        let Some(mut rewind) = self.rewind.take() else {
+            return false;
+        };
+        let rewound = rewind.rewind(self, self.sim_time());
+        self.rewind = Some(rewind);
+
+        if rewound {
+            self.lock_deadline = None;
+            self.reset_lock_delay();
+            self.desperation_hint = None;
+            self.show_popup("REWIND!");
+        }
+        rewound
+    }
+
+    // Queues `lines` rows of opponent garbage to rise after `settings.garbage_delay`
+    fn receive_garbage(&mut self, lines: u32) {
+        if lines > 0 {
+            let rise_time = self.sim_time() + self.settings.garbage_delay;
+            self.pending_garbage.push_back((lines, rise_time));
+        }
+    }
+
+    // Offsets `attack` against pending garbage, oldest packets first, and
+    // returns what's left over to send
+    fn cancel_pending_garbage(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            let Some((lines, _)) = self.pending_garbage.front_mut() else {
+                break;
+            };
+            let canceled = attack.min(*lines);
+            *lines -= canceled;
+            attack -= canceled;
+            if *lines == 0 {
+                self.pending_garbage.pop_front();
+            }
+        }
+        attack
+    }
+
+    // Raises every pending packet whose delay has run out. Packets keep their
+    // own timers, so a later one can still be canceled after an earlier rose.
+    fn rise_pending_garbage(&mut self, now: f64) {
+        while let Some(&(lines, rise_time)) = self.pending_garbage.front() {
+            if rise_time > now {
+                break;
+            }
+            self.pending_garbage.pop_front();
+            self.add_random_garbage(lines.min(u8::MAX as u32) as u8, INCOMING_GAP_DENSITY);
+        }
+    }
+
+    fn pending_garbage_lines(&self) -> u32 {
+        self.pending_garbage.iter().map(|(lines, _)| lines).sum()
+    }
+
+    fn take_outgoing_garbage(&mut self) -> u32 {
+        std::mem::take(&mut self.outgoing_garbage)
+    }
+
+    // Saves what's on screen right now, this game included, as a PNG. Call
+    // after everything for the frame is drawn.
+    fn export_to_png(&self, path: &std::path::Path) -> std::io::Result<()> {
+        share::save_png(&get_screen_data(), path).map_err(std::io::Error::other)
+    }
+
+    // Optional files that fail to load are left out of the game; say why
+    fn report_load_error(&mut self, err: &TetrusError) {
+        eprintln!("Couldn't load {}", err);
+        self.show_popup(&format!("Couldn't load {}", err));
+    }
+
+    fn show_popup(&mut self, text: &str) {
+        self.popup = Some((text.to_string(), wall_time()));
+    }
+
+    // Raises `lines` rows of garbage, then resolves the falling piece according
+    // to `settings.garbage_shift`. `gap_density` is the fraction of each row left
+    // empty: 0.1 gives the one-hole "cheese" rows, 0.5 half-empty rows. Every row
+    // keeps at least one gap and one filled cell.
+    fn add_random_garbage(&mut self, lines: u8, gap_density: f32) {
+        for _ in 0..lines {
+            let gaps = self.garbage_gaps(gap_density);
+            if !self.board.push_garbage_row(&gaps) {
+                self.game_over = true;
+            }
+        }
+
+        if self.settings.garbage_shift == GarbageShift::Sticky {
+            self.current_piece.translate(0, -(lines as i32));
+        }
+
+        self.push_out_of_stack();
+    }
+
+    // Gap columns for one garbage row: the next from the dig pattern if there
+    // is one, otherwise about `gap_density` of the row at random
+    fn garbage_gaps(&mut self, gap_density: f32) -> Vec<usize> {
+        if let Some(pattern) = &mut self.dig_pattern {
+            return vec![pattern.next_gap()];
+        }
+
+        // Round the expected gap count up or down at random so fractional
+        // densities still average out right
+        let width = self.board.width();
+        let expected = gap_density.clamp(0.0, 1.0) * width as f32;
+        let mut gap_count = expected.floor() as usize;
+        if self.rng.gen::<f32>() < expected.fract() {
+            gap_count += 1;
+        }
+        let gap_count = gap_count.clamp(1, width - 1);
+        sample(&mut self.rng, width, gap_count).into_vec()
+    }
+
+    // Pushes the falling piece straight up until it no longer overlaps the
+    // stack. Poking out of the top of the board this way is a top-out.
+    fn push_out_of_stack(&mut self) {
+        let mut pushed = false;
+        while !self.can_move(self.current_piece.x, self.current_piece.y) {
+            self.current_piece.translate(0, -1);
+            pushed = true;
+        }
+        if pushed && self.current_piece.y < 0 {
+            self.game_over = true;
+        }
+    }
+
+    // Flips the locked grid horizontally and moves the falling piece to the
+    // mirrored column. The piece's own shape is left as is.
+    fn mirror_board(&mut self) {
+        self.board.mirror();
+
+        let width = self.current_piece.shape[0].len() as i32;
+        self.current_piece.x = self.board.width() as i32 - width - self.current_piece.x;
+        self.push_out_of_stack();
+    }
+
+    // A game set up as `config` says. Files it names are loaded separately.
+    fn new_with_config(config: &GameConfig) -> Self {
+        let (cols, rows) = config.grid_dimensions;
+        let mut game = Self::new(cols, rows);
+        game.block_size = config.block_size;
+        game.settings.ghost_alpha = config.ghost_alpha;
+        game.settings.menu_keys = config.menu_keys;
+        game.piece_gravity = config.piece_gravity;
+        game.clock_left = config.clock_start;
+        game.clock_bonus = config.clock_bonus;
+        if config.randomizer != Randomizer::SevenBag {
+            game.sequence.set_randomizer(config.randomizer);
+            game.spawn_next_piece();
+        }
+        // Needed before set_mode, which raises the drill's first rows
+        if let Some(path) = &config.dig_pattern {
+            match DigPattern::load(path, cols) {
+                Ok(pattern) => game.dig_pattern = Some(pattern),
+                Err(err) => game.report_load_error(&err.into()),
+            }
+        }
+        game.set_mode(config.mode);
+        game
+    }
+
+    fn new(cols: usize, rows: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(thread_rng().gen());
+        let mut sequence = PieceSequence::new(StdRng::seed_from_u64(rng.gen()));
+        Self {
+            mode: GameMode::default(),
+            board: Board::new(cols, rows),
+            current_piece: Self::spawn_new_piece(sequence.next(), cols),
+            sequence,
+            hold: None,
+            hold_used: false,
+            last_fall: 0.0,
+            lock_deadline: None,
+            soft_drop_lock_at: None,
+            rested: 0.0,
+            lock_resets: 0,
+            lowest_row: 0,
+            block_size: 30.0,
+            soft_dropping: false,
+            settings: Settings::default(),
+            desperation_hint: None,
+            game_over: false,
+            won: false,
+            combo: 0,
+            score: 0,
+            outgoing_garbage: 0,
+            pending_garbage: VecDeque::new(),
+            penalty_shown_at: None,
+            stats: GameStats::default(),
+            popup: None,
+            ticks: 0,
+            zen_timer: 0.0,
+            speed_multiplier: 1.0,
+            last_clear_at: None,
+            speed_decay_timer: 0.0,
+            rewind: None,
+            dig_pattern: None,
+            last_dig_rise: 0.0,
+            drop_trails: Vec::new(),
+            motion_trail: VecDeque::new(),
+            tetris_animation: None,
+            hold_animation: None,
+            sound_events: Vec::new(),
+            isolated_holes: false,
+            show_debug: false,
+            input_accumulator: 0.0,
+            queued_actions: Vec::new(),
+            fresh_spawn: true,
+            modifiers: Modifiers::default(),
+            piece_gravity: [1.0; 7],
+            clock_left: SURVIVAL_CLOCK_START,
+            clock_bonus: SURVIVAL_CLOCK_BONUS,
+            last_move_was_rotation: false,
+            last_rotation_state: 0,
+            lock_results: None,
+            replay: Replay::new(cols, rows),
+            training_ghost: None,
+            pb_pace: None,
+            spawn_row: None,
+            piece_presses: 0,
+            best: None,
+            new_record: false,
+            rng,
+        }
+    }
+
+    // First board row shown in the playfield. Boards taller than the window
+    // scroll to keep the falling piece in view.
+    fn view_top(&self) -> usize {
+        let max_top = self.board.height().saturating_sub(VISIBLE_ROWS);
+        (self.current_piece.y - 4).clamp(0, max_top as i32) as usize
+    }
+}
+
+// Seconds since startup, for what's timed on screen rather than in the
+// simulation. Tests run without a window, where macroquad has no clock.
+#[cfg(not(test))]
+fn wall_time() -> f64 {
+    get_time()
+}
+
+#[cfg(test)]
+fn wall_time() -> f64 {
+    0.0
+}
+
+// The configured sound pack, if there is one
+async fn load_sound_set(config: &GameConfig) -> Result<Option<SoundSet>, TetrusError> {
+    let Some(dir) = &config.sound_set else {
+        return Ok(None);
+    };
+    Ok(Some(SoundSet::load_from_dir(std::path::Path::new(dir)).await?))
+}
+
+// The configured training ghost, if there is one
+fn load_training_ghost(config: &GameConfig) -> Result<Option<TrainingGhost>, TetrusError> {
+    let Some(path) = &config.ghost else {
+        return Ok(None);
+    };
+    let (cols, rows) = config.grid_dimensions;
+    Ok(Some(TrainingGhost::new(Replay::load(path, cols, rows)?)))
+}
+
+// The configured personal best to pace against, if there is one
+fn load_pb_pace(config: &GameConfig) -> Result<Option<PbPace>, TetrusError> {
+    let Some(path) = &config.pb_replay else {
+        return Ok(None);
+    };
+    let (cols, rows) = config.grid_dimensions;
+    Ok(Some(PbPace::new(&Replay::load(path, cols, rows)?)))
+}
+
+// The configured starting field, if there is one, rows top to bottom
+fn load_field(config: &GameConfig) -> Result<Option<Vec<Vec<Cell>>>, TetrusError> {
+    let Some(code) = &config.field else {
+        return Ok(None);
+    };
+    let (cols, rows) = config.grid_dimensions;
+    Ok(Some(field_code::decode(code, cols, rows)?))
+}
+
+// Attaches the configured training ghost, PB pace and starting field
+fn load_extras(game_state: &mut GameState, config: &GameConfig) {
+    match load_training_ghost(config) {
+        Ok(ghost) => game_state.training_ghost = ghost,
+        Err(err) => game_state.report_load_error(&err),
+    }
+    match load_pb_pace(config) {
+        Ok(pace) => game_state.pb_pace = pace,
+        Err(err) => game_state.report_load_error(&err),
+    }
+    match load_field(config) {
+        Ok(Some(field)) => game_state.board.load_field(field),
+        Ok(None) => {}
+        Err(err) => game_state.report_load_error(&err),
+    }
+}
+
+// The computer opponent for VersusCpu games
+fn new_cpu(config: &GameConfig) -> Option<CpuPlayer> {
+    let GameMode::VersusCpu { cpu_level } = config.mode else {
+        return None;
+    };
+    let (cols, rows) = config.grid_dimensions;
+    let mut cpu = CpuPlayer::new(cols, rows, cpu_level);
+    // Drawn with the player's layout
+    cpu.game.block_size = config.block_size;
+    Some(cpu)
+}
+
+// The configured saved game, if there is one, in `GameState::to_json` form
+fn load_saved_state(config: &GameConfig) -> Result<Option<GameState>, TetrusError> {
+    let Some(path) = &config.state else {
+        return Ok(None);
+    };
+    let json = std::fs::read_to_string(path)?;
+    let state = GameState::from_json(&json)?;
+    // The extras loaded next, and the board size in the config, assume it
+    let (cols, rows) = config.grid_dimensions;
+    state.board.check_size(cols, rows)?;
+    Ok(Some(state))
+}
+
+#[macroquad::main("Tetris")]
+async fn main() {
+    let config = GameConfig::load();
+    let mode = config.mode;
+    let (cols, rows) = config.grid_dimensions;
+
+    let mut loader = AssetLoader::new();
+    while !loader.poll() {
+        loader.draw();
+        next_frame().await
+    }
+    let assets = loader.finish();
+
+    if mode == GameMode::TwoPlayer {
+        two_player::run(cols, rows, config.menu_keys, &assets).await;
+        return;
+    }
+    if mode == GameMode::FractionalPhysics {
+        fractional::run(cols, rows, config.block_size, config.menu_keys, &assets).await;
+        return;
+    }
+
+    let mut game_state = GameState::new_with_config(&config);
+    match load_saved_state(&config) {
+        Ok(Some(mut state)) => {
+            // The saved game, with this session's look and feel
+            state.block_size = game_state.block_size;
+            state.settings.ghost_alpha = game_state.settings.ghost_alpha;
+            state.settings.menu_keys = game_state.settings.menu_keys;
+            state.piece_gravity = game_state.piece_gravity;
+            game_state = state;
+        }
+        Ok(None) => {}
+        Err(err) => game_state.report_load_error(&err),
+    }
+    let mode = game_state.mode;
+
+    // Sound is off unless a sound pack is given
+    let sounds = load_sound_set(&config).await.unwrap_or_else(|err| {
+        game_state.report_load_error(&err);
+        None
+    });
+    load_extras(&mut game_state, &config);
+    let mut settings_screen = SettingsScreen::default();
+    let mut bindings = match mode.gravity_direction() {
+        GravityDirection::Down => KeyBindings::single_player(),
+        GravityDirection::Right => KeyBindings::sideways(),
+    };
+    let menu_bindings = KeyBindings::menu(config.menu_keys);
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = gamepad::Gamepads::new(config.pad_bindings.clone());
+    let mut pause_menu = PauseMenu::new();
+    let mut profile_screen = ProfileScreen::new(config.utc_offset);
+    let mut history_recorded = false;
+    let mut spectators = config.spectate_port.and_then(|port| match SpectatorServer::bind(port) {
+        Ok(server) => Some(server),
+        Err(err) => {
+            eprintln!("Couldn't start spectator server: {}", err);
+            game_state.show_popup(&format!("Couldn't start spectator server: {}", err));
+            None
+        }
+    });
+    // Pieces placed and game over as last sent to spectators
+    let mut spectated = (0, false);
+    let mut records = Records::load();
+    game_state.best = records.get(&mode.record_key());
+    let mut pending_share_card: Option<ShareCard> = None;
+    // Real time not yet simulated, in seconds
+    let mut accumulator = 0.0;
+    let mut cpu = new_cpu(&config);
+    
+    // Calculate window size based on game grid
+    let layout = Layout::new(&game_state);
+    let (mut window_width, window_height) = layout.window_size();
+    // The CPU's board goes to the right of the player's
+    let cpu_board_x = window_width;
+    if cpu.is_some() {
+        window_width += layout.playfield_width();
+    }
+    
+    request_new_screen_size(window_width, window_height);
+
+    loop {
+        // Cards are rendered a frame before they can be read back
+        if let Some(card) = pending_share_card.take() {
+            match card.save() {
+                Ok(()) => game_state.show_popup(&format!("Saved {}", card.path())),
+                Err(err) => game_state.show_popup(&format!("Couldn't save card: {}", err)),
+            }
+        }
+
+        if game_state.game_over && is_key_pressed(KeyCode::C) {
+            let summary = game_state.stats.to_emoji_summary(&game_state.board, game_state.score, game_state.mode);
+            miniquad::window::clipboard_set(&summary);
+            game_state.show_popup("Copied!");
+        }
+
+        if game_state.game_over && is_key_pressed(KeyCode::S) {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let path = format!("tetrust_share_{}.png", timestamp);
+            pending_share_card = Some(ShareCard::render(&game_state, path));
+        }
+
+        clear_background(BLACK);
+
+        // Controllers feed the same actions as the keyboard, menu ones included
+        #[cfg(feature = "gamepad")]
+        let pad_actions = {
+            let input = gamepads.poll();
+            for notice in &input.notices {
+                game_state.show_popup(notice);
+            }
+            input.actions
+        };
+        #[cfg(not(feature = "gamepad"))]
+        let pad_actions: Vec<Action> = Vec::new();
+        let (pad_menu_actions, pad_game_actions): (Vec<Action>, Vec<Action>) =
+            pad_actions.into_iter().partition(Action::is_menu);
+
+        let mut restart = false;
+        for action in menu_bindings.poll().into_iter().chain(pad_menu_actions) {
+            match pause_menu.handle(action, game_state.game_over) {
+                Some(MenuCommand::Restart) => restart = true,
+                Some(MenuCommand::Quit) => return,
+                None => {}
+            }
+        }
+        if restart {
+            // A fresh game with this session's settings
+            let old = std::mem::replace(&mut game_state, GameState::new_with_config(&config));
+            game_state.settings = old.settings;
+            game_state.best = records.get(&mode.record_key());
+            load_extras(&mut game_state, &config);
+            cpu = new_cpu(&config);
+            history_recorded = false;
+            accumulator = 0.0;
+        }
+
+        for event in game_state.sound_events.drain(..) {
+            if let Some(sounds) = &sounds {
+                sounds.play(event);
+            }
+        }
+
+        // Spectators hear about every lock, which covers clears, and the end
+        if let Some(server) = &mut spectators {
+            let now = (game_state.stats.pieces_placed, game_state.game_over);
+            if now != spectated {
+                server.broadcast(&game_state.to_json());
+                spectated = now;
+            }
+            server.poll();
+        }
+
+        if game_state.game_over && !history_recorded {
+            history_recorded = true;
+            if let Some(sounds) = &sounds {
+                sounds.play_game_over();
+            }
+            let grade = ChallengeScore::new(&game_state.stats, game_state.board.width()).grade();
+            if let Err(err) = history::record_game(&game_state.stats, game_state.score, grade) {
+                game_state.show_popup(&format!("Couldn't save history: {}", err));
+            }
+
+            if let Some(path) = &config.record_replay {
+                if let Err(err) = game_state.replay.save(path) {
+                    game_state.show_popup(&format!("Couldn't save replay: {}", err));
+                }
+            }
+
+            let win_time = game_state.won.then_some(game_state.stats.elapsed);
+            game_state.new_record = records.submit(&mode.record_key(), game_state.score, win_time);
+            let new_best_combo = records.submit_combo(&mode.record_key(), game_state.stats.max_combo);
+            if game_state.new_record || new_best_combo {
+                if let Err(err) = records.save() {
+                    game_state.show_popup(&format!("Couldn't save records: {}", err));
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::Tab) {
+            settings_screen.open = !settings_screen.open;
+        }
+        if is_key_pressed(KeyCode::P) {
+            profile_screen.toggle();
+        }
+
+        if settings_screen.open {
+            settings_screen.handle_input(&mut game_state.settings);
+        } else if !profile_screen.open
+            && !pause_menu.is_open()
+            && !game_state.game_over
+            && game_state.tetris_animation.is_none()
+        {
+            bindings.set_rotate_slide_key(game_state.settings.rotate_slide_key);
+            let mut actions = bindings.poll();
+            actions.extend(pad_game_actions);
+            game_state.queue_actions(actions);
+            if game_state.input_fps_limit(game_state.settings.max_input_hz) {
+                let actions = std::mem::take(&mut game_state.queued_actions);
+                game_state.apply_actions(&actions);
+            }
+
+            // Step the simulation for the real time that passed since last frame
+            accumulator += (get_frame_time() as f64).min(MAX_DELTA_MS / 1000.0);
+            while accumulator >= TICK && !game_state.game_over {
+                accumulator -= TICK;
+                // Dump the board to the console if the step panics, to help
+                // reproduce whatever went wrong
+                let step = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| game_state.tick()));
+                if let Err(panic) = step {
+                    game_state.board.print_debug();
+                    std::panic::resume_unwind(panic);
+                }
+
+                if let Some(cpu) = cpu.as_mut() {
+                    cpu.tick();
+                    ai::exchange_garbage(&mut game_state, &mut cpu.game);
+                    // First to top out loses
+                    if cpu.game.game_over && !game_state.game_over {
+                        game_state.won = true;
+                        game_state.game_over = true;
+                    }
+                }
+            }
+        }
+
+        if game_state.settings.animated_backgrounds {
+            // Held still for reduced motion
+            let t = if game_state.settings.animations { get_time() } else { 0.0 };
+            background::draw_level_background(game_state.level(), t);
+        } else if let Some(background) = &assets.background {
+            render::draw_background(background);
+        }
+        game_state.update_motion_trail(get_frame_time());
+        game_state.update_hold_animation(get_frame_time());
+        if !pause_menu.is_open() {
+            game_state.update_tetris_animation(get_frame_time());
+        }
+        render::draw_game_at(&game_state, &layout, 0.0);
+        game_state.fresh_spawn = false;
+        if let Some(cpu) = &cpu {
+            render::draw_opponent(&cpu.game, &layout, cpu_board_x, &format!("CPU LV {}", cpu.level));
+        }
+
+        if profile_screen.open {
+            profile_screen.draw(game_state.block_size, game_state.block_size * 2.0, game_state.settings.overlay_dim);
+        }
+        if settings_screen.open {
+            settings_screen.draw(&game_state.settings, game_state.block_size, game_state.block_size * 2.0);
+        }
+        pause_menu.draw(&menu_bindings, game_state.block_size, game_state.block_size * 2.0, game_state.settings.overlay_dim);
+
+        // One line of JSON per press, for tools reading stdout
+        if is_key_pressed(KeyCode::F9) {
+            println!("{}", game_state.to_json());
+        }
+
+        if is_key_pressed(KeyCode::F12) {
+            let path = share::screenshot_path();
+            match game_state.export_to_png(std::path::Path::new(&path)) {
+                Ok(()) => game_state.show_popup(&format!("Saved {}", path)),
+                Err(err) => game_state.show_popup(&format!("Couldn't save screenshot: {}", err)),
+            }
+        }
+
+        next_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A game with an O piece resting on the floor of an empty board
+    fn landed_game() -> GameState {
+        let mut game = GameState::new(10, 20);
+        game.settings.lock_delay = 0.5;
+        game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+        while game.current_piece.try_translate(0, 1, &game.board) {}
+        game.reset_lock_delay();
+        game
+    }
+
+    // Ticks until the current piece locks, giving up after `limit`
+    fn ticks_to_lock(game: &mut GameState, limit: u32) -> Option<u32> {
+        let placed = game.stats.pieces_placed;
+        (1..=limit).find(|_| {
+            game.tick();
+            game.stats.pieces_placed > placed
+        })
+    }
+
+    #[test]
+    fn moves_stop_restarting_the_lock_delay_after_the_cap() {
+        let mut game = landed_game();
+        for i in 0..MAX_LOCK_RESETS + 5 {
+            game.tick();
+            let action = if i % 2 == 0 { Action::MoveLeft } else { Action::MoveRight };
+            game.apply_actions(&[action]);
+        }
+        assert_eq!(game.stats.pieces_placed, 0);
+        assert_eq!(game.lock_resets, MAX_LOCK_RESETS);
+
+        // Only what's left of the last delay remains
+        let left = ticks_to_lock(&mut game, 60).unwrap();
+        assert!(left < 30, "locked after {} ticks", left);
+    }
+
+    #[test]
+    fn relanding_resumes_the_lock_delay() {
+        let mut game = landed_game();
+        game.lock_resets = MAX_LOCK_RESETS;
+        for _ in 0..20 {
+            game.tick();
+        }
+        let rested = game.rested;
+
+        // Kicked up off the stack, then back down onto the same row
+        game.current_piece.y -= 1;
+        game.tick();
+        assert_eq!(game.rested, rested);
+        game.current_piece.y += 1;
+        game.apply_actions(&[Action::MoveLeft]);
+        assert_eq!(game.rested, rested);
+
+        let left = ticks_to_lock(&mut game, 60).unwrap();
+        assert!(left <= 11, "locked after {} ticks", left);
+    }
+
+    // A board whose bottom row is filled in every other column, so an O
+    // covers a hole wherever it lands
+    fn comb_game(piece_type: PieceType) -> GameState {
+        let mut game = GameState::new(10, 20);
+        for x in (0..10).step_by(2) {
+            game.board.fill_garbage(x, 19);
+        }
+        game.current_piece = GameState::spawn_new_piece(piece_type, 10);
+        game
+    }
+
+    #[test]
+    fn auto_hold_holds_a_piece_with_no_clean_drop() {
+        let mut game = comb_game(PieceType::O);
+        game.settings.auto_hold = true;
+        game.auto_hold();
+        assert!(game.hold == Some(PieceType::O));
+        assert!(game.hold_used);
+    }
+
+    #[test]
+    fn auto_hold_leaves_pieces_that_fit() {
+        let mut game = comb_game(PieceType::I);
+        game.settings.auto_hold = true;
+        game.auto_hold();
+        assert!(game.hold.is_none());
+    }
+
+    #[test]
+    fn auto_hold_is_off_by_default() {
+        let mut game = comb_game(PieceType::O);
+        game.auto_hold();
+        assert!(game.hold.is_none());
+    }
+
+    #[test]
+    fn hold_is_off_in_hardcore_and_puzzles() {
+        for mode in [GameMode::Hardcore, GameMode::Puzzle { number: 1 }] {
+            let mut game = GameState::new(10, 20);
+            game.mode = mode;
+            assert!(!game.hold_piece());
+            assert!(game.hold.is_none());
+        }
+        assert!(GameState::new(10, 20).hold_piece());
+    }
+
+    #[test]
+    fn gravity_drops_several_rows_a_tick_at_high_levels() {
+        let mut game = GameState::new(10, 20);
+        game.stats.lines_cleared = 14 * LINES_PER_LEVEL;
+        let y = game.current_piece.y;
+        game.tick();
+        assert!(game.current_piece.y > y + 1, "fell {} rows", game.current_piece.y - y);
+
+        // Level 20 is 20G: the piece is on the floor after a tick
+        game.stats.lines_cleared = 19 * LINES_PER_LEVEL;
+        game.tick();
+        assert!(!game.can_move(game.current_piece.x, game.current_piece.y + 1));
+    }
+
+    #[test]
+    fn gravity_keeps_the_leftover_time() {
+        let mut game = GameState::new(10, 20);
+        let y = game.current_piece.y;
+        let ticks = (game.level_fall_interval() / TICK).ceil() as u32;
+        for _ in 0..ticks * 3 {
+            game.tick();
+        }
+        assert_eq!(game.current_piece.y, y + 3);
+    }
+
+    #[test]
+    fn sacrifice_clears_what_the_piece_had_pending() {
+        let mut game = GameState::new(10, 20);
+        game.mode = GameMode::Casual;
+        game.score = 1000;
+        game.hold_piece();
+        game.lock_deadline = Some(1.0);
+        game.soft_drop_lock_at = Some(1.0);
+        for _ in 0..30 {
+            game.tick();
+        }
+
+        assert!(game.sacrifice_piece());
+        assert!(game.lock_deadline.is_none());
+        assert!(game.soft_drop_lock_at.is_none());
+        assert!(!game.hold_used);
+        assert_eq!(game.penalty_shown_at, Some(game.sim_time()));
+        assert_eq!(game.score, 1000 - SACRIFICE_PENALTY);
+    }
+
+    #[test]
+    fn ticks_until_lock_counts_the_rows_left_to_fall() {
+        let mut game = GameState::new(10, 20);
+        game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+        let y = game.current_piece.y;
+        assert_eq!(game.ticks_until_lock(y), (18 - y) as u32);
+
+        let x = game.current_piece.x as usize;
+        game.board.fill_garbage(x, 10);
+        assert_eq!(game.ticks_until_lock(y), (8 - y) as u32);
+        assert_eq!(game.ticks_until_lock(8), 0);
+    }
+
+    #[test]
+    fn a_new_lowest_row_restores_the_resets() {
+        let mut game = landed_game();
+        game.current_piece.y -= 3;
+        game.reset_lock_delay();
+        game.lock_resets = MAX_LOCK_RESETS;
+        game.rested = 0.3;
+
+        game.current_piece.y += 1;
+        game.tick();
+        assert_eq!(game.lock_resets, 0);
+        assert_eq!(game.rested, 0.0);
+    }
+
+    #[test]
+    fn desperation_clear_is_offered_only_near_the_top() {
+        let mut game = GameState::new(10, 20);
+        for x in 0..6 {
+            game.board.fill_garbage(x, 19);
+        }
+        game.current_piece = GameState::spawn_new_piece(PieceType::I, 10);
+        assert!(game.find_desperation_clear().is_none());
+
+        for y in 3..19 {
+            game.board.fill_garbage(0, y);
+        }
+        let placement = game.find_desperation_clear().unwrap();
+        assert_eq!(placement.lines_cleared, 1);
+        assert!(placement.x >= 6);
+    }
+
+    #[test]
+    fn garbage_shift_keeps_or_carries_the_piece() {
+        for (shift, expected_y) in [(GarbageShift::Naive, 10), (GarbageShift::Sticky, 8)] {
+            let mut game = GameState::new(10, 20);
+            game.settings.garbage_shift = shift;
+            game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+            game.current_piece.y = 10;
+            game.add_random_garbage(2, 0.1);
+            assert_eq!(game.current_piece.y, expected_y);
+            assert!(!game.game_over);
+        }
+
+        // Garbage rising into a naive piece pushes it up out of the stack
+        let mut game = GameState::new(10, 20);
+        game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+        game.current_piece.y = 17;
+        game.add_random_garbage(3, 0.1);
+        assert!(game.current_piece.y <= 15);
+        assert!(game.can_move(game.current_piece.x, game.current_piece.y));
+    }
+
+    #[test]
+    fn mirror_board_flips_the_stack_and_the_piece_column() {
+        let mut game = GameState::new(10, 20);
+        game.board.fill_garbage(0, 19);
+        game.board.fill_garbage(1, 18);
+        game.current_piece = GameState::spawn_new_piece(PieceType::L, 10);
+        game.current_piece.x = 0;
+        let shape = game.current_piece.shape.clone();
+
+        game.mirror_board();
+        assert!(game.board.grid[19][9].is_filled());
+        assert!(game.board.grid[18][8].is_filled());
+        assert!(!game.board.grid[19][0].is_filled());
+        assert_eq!(game.current_piece.x, 10 - shape[0].len() as i32);
+        assert!(game.current_piece.shape == shape);
+
+        game.mirror_board();
+        assert!(game.board.grid[19][0].is_filled());
+        assert_eq!(game.current_piece.x, 0);
+    }
+
+    #[test]
+    fn random_garbage_rows_keep_a_gap_and_a_block() {
+        for density in [0.0, 0.5, 1.0] {
+            let mut game = GameState::new(10, 20);
+            game.add_random_garbage(5, density);
+            let filled: Vec<usize> =
+                game.board.grid.iter().map(|row| row.iter().filter(|cell| cell.is_filled()).count()).collect();
+            assert!(filled[..15].iter().all(|&count| count == 0));
+            for &count in &filled[15..] {
+                assert!((1..10).contains(&count), "{} filled at density {}", count, density);
+            }
+        }
+
+        let mut game = GameState::new(10, 20);
+        game.add_random_garbage(4, 0.5);
+        let gaps = game.board.grid[16..].iter().flatten().filter(|cell| !cell.is_filled()).count();
+        assert_eq!(gaps, 20);
+    }
+
+    #[test]
+    fn random_garbage_averages_out_to_the_gap_density() {
+        // 0.25 of 10 columns rounds to 2 or 3 gaps at random
+        for (density, expected) in [(0.1, 1.0), (0.25, 2.5)] {
+            let mut game = GameState::new(10, 1000);
+            game.rng = StdRng::seed_from_u64(42);
+            for _ in 0..4 {
+                game.add_random_garbage(250, density);
+            }
+            let gaps = game.board.grid.iter().flatten().filter(|cell| !cell.is_filled()).count();
+            let average = gaps as f64 / 1000.0;
+            assert!((average - expected).abs() < 0.1, "{} gaps a row at density {}", average, density);
+        }
+    }
+
+    #[test]
+    fn zero_delays_lock_a_grounded_piece_on_the_next_tick() {
+        let mut game = landed_game();
+        game.settings.zero_delays = true;
+        assert_eq!(ticks_to_lock(&mut game, 5), Some(1));
+
+        // The lock delay applies as usual with the setting off
+        let mut game = landed_game();
+        assert!(ticks_to_lock(&mut game, 20).is_none());
+    }
+
+    // The bottom `rows` rows filled but for the two columns an O spawns over
+    fn well_game(rows: usize) -> GameState {
+        let mut game = GameState::new(10, 20);
+        for y in 20 - rows..20 {
+            for x in (0..10).filter(|x| !(4..6).contains(x)) {
+                game.board.fill_garbage(x, y);
+            }
+        }
+        game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+        game
+    }
+
+    #[test]
+    fn a_double_counts_toward_the_attack_sent() {
+        let mut game = well_game(2);
+        game.hard_drop();
+        assert_eq!(game.stats.lines_cleared, 2);
+        assert_eq!(game.stats.garbage_sent, 1);
+        assert_eq!(game.take_outgoing_garbage(), 1);
+    }
+
+    #[test]
+    fn hard_drop_locks_at_once_or_after_the_grace() {
+        let mut game = GameState::new(10, 20);
+        game.hard_drop();
+        assert_eq!(game.stats.pieces_placed, 1);
+
+        let mut game = GameState::new(10, 20);
+        game.settings.hard_drop_lock = HardDropLock::Delayed;
+        game.hard_drop();
+        assert_eq!(game.stats.pieces_placed, 0);
+        let x = game.current_piece.x;
+        game.apply_actions(&[Action::MoveLeft]);
+        assert_eq!(game.current_piece.x, x - 1);
+
+        let grace = (HARD_DROP_LOCK_DELAY / TICK).ceil() as u32;
+        let ticks = ticks_to_lock(&mut game, grace + 2).unwrap();
+        assert!(ticks >= grace - 1, "locked after {} ticks", ticks);
+    }
+
+    #[test]
+    fn rotate_and_slide_ends_against_the_wall() {
+        let mut game = GameState::new(10, 20);
+        game.current_piece = GameState::spawn_new_piece(PieceType::T, 10);
+        game.rotate_and_slide(-1);
+        assert_eq!(game.current_piece.rotation, 1);
+        assert!(!game.can_move(game.current_piece.x - 1, game.current_piece.y));
+        assert_eq!(game.current_piece.x, 0);
+        assert!(!game.last_move_was_rotation);
+
+        game.rotate_and_slide(1);
+        let width = game.current_piece.shape[0].len() as i32;
+        assert_eq!(game.current_piece.rotation, 2);
+        assert_eq!(game.current_piece.x + width, 10);
+    }
+
+    #[test]
+    fn the_fall_interval_floor_only_binds_at_high_levels() {
+        let mut game = GameState::new(10, 20);
+        game.settings.min_fall_interval = 0.02;
+        assert_eq!(game.effective_fall_speed(), game.level_fall_interval());
+
+        game.stats.lines_cleared = 19 * LINES_PER_LEVEL;
+        assert!(game.level_fall_interval() < 0.02);
+        assert_eq!(game.effective_fall_speed(), 0.02);
+        game.soft_dropping = true;
+        assert_eq!(game.effective_fall_speed(), 0.02);
+
+        // Off, the curve plays out to the top
+        game.settings.min_fall_interval = 0.0;
+        assert_eq!(game.effective_fall_speed(), game.level_fall_interval());
+    }
+
+    #[test]
+    fn flood_fill_counts_the_connected_empty_cells() {
+        let mut game = GameState::new(4, 4);
+        // A sealed-off corner pocket of two cells under a roof
+        for (x, y) in [(0, 2), (1, 2), (2, 2), (2, 3)] {
+            game.board.fill_garbage(x, y);
+        }
+        assert_eq!(game.flood_fill_accessible(0, 3), 2);
+        assert_eq!(game.flood_fill_accessible(0, 0), 10);
+        assert_eq!(game.flood_fill_accessible(2, 2), 0);
+    }
+
+    #[test]
+    fn garbage_waits_out_the_delay_and_clears_cancel_it() {
+        let mut game = well_game(2);
+        game.receive_garbage(3);
+        game.hard_drop();
+        assert_eq!(game.pending_garbage_lines(), 2);
+        assert_eq!(game.take_outgoing_garbage(), 0);
+        assert_eq!(game.cancel_pending_garbage(5), 3);
+        assert_eq!(game.pending_garbage_lines(), 0);
+
+        game.receive_garbage(2);
+        let delay = (game.settings.garbage_delay / TICK).round() as u32;
+        for _ in 0..delay - 1 {
+            game.tick();
+        }
+        assert_eq!(game.pending_garbage_lines(), 2);
+        game.tick();
+        game.tick();
+        assert_eq!(game.pending_garbage_lines(), 0);
+        assert_eq!(game.board.max_stack_height(), 2);
+    }
+
+    #[test]
+    fn symmetry_matches_the_distinct_rotations() {
+        let expected = [
+            (PieceType::I, Symmetry::Half),
+            (PieceType::J, Symmetry::None),
+            (PieceType::L, Symmetry::None),
+            (PieceType::O, Symmetry::Full),
+            (PieceType::S, Symmetry::Half),
+            (PieceType::T, Symmetry::None),
+            (PieceType::Z, Symmetry::Half),
+        ];
+        for (piece_type, symmetry) in expected {
+            assert!(piece_type.symmetry() == symmetry, "{}", piece_type.letter());
+            let shape = GameState::get_piece_shape(piece_type);
+            let rotations = GameState::all_rotations(&shape, piece_type);
+            assert_eq!(rotations.len(), symmetry.distinct_rotations());
+            for (i, rotation) in rotations.iter().enumerate() {
+                assert!(rotations[..i].iter().all(|earlier| earlier != rotation));
+            }
+        }
+    }
+
+    #[test]
+    fn projected_clear_score_is_the_best_drop_available() {
+        let mut game = well_game(2);
+        let double = scoring::line_clear_score(game.settings.scoring_mode, 2, 1);
+        assert_eq!(game.projected_clear_score(), double);
+
+        game.current_piece = GameState::spawn_new_piece(PieceType::I, 10);
+        assert_eq!(game.projected_clear_score(), 0);
+        assert_eq!(GameState::new(10, 20).projected_clear_score(), 0);
+    }
+
+    #[test]
+    fn a_blocked_rotation_is_left_undone_and_described() {
+        // An I lying in the only open row of a full 4x4 well
+        let mut game = GameState::new(4, 4);
+        for y in 0..3 {
+            for x in 0..4 {
+                game.board.fill_garbage(x, y);
+            }
+        }
+        game.current_piece = GameState::spawn_new_piece(PieceType::I, 4);
+        game.current_piece.x = 0;
+        game.current_piece.y = 3;
+        game.rotate_piece();
+        assert_eq!(game.current_piece.rotation, 0);
+        assert_eq!(game.current_piece.shape.len(), 1);
+
+        let entry = game.failed_rotation_entry();
+        assert!(entry.starts_with("rotation failed: piece I rotation 0 at (0, 3)\nfumen -\n████\n"));
+        assert!(entry.ends_with("  2 ####\n  3 ....\n"));
+    }
+
+    #[test]
+    fn an_imported_sequence_is_dealt_in_order() {
+        let mut game = GameState::new(10, 20);
+        let seq = [PieceType::Z, PieceType::Z, PieceType::I];
+        game.import_piece_sequence(&seq);
+        assert!(game.current_piece.piece_type == PieceType::Z);
+        assert!(game.sequence.peek(2) == seq[1..]);
+
+        let mut dealt = Vec::new();
+        for _ in 0..2 {
+            game.hard_drop();
+            dealt.push(game.current_piece.piece_type);
+        }
+        assert!(dealt == seq[1..]);
+        assert_eq!(game.sequence.override_remaining(), 0);
+    }
+
+    #[test]
+    fn an_empty_batch_changes_nothing() {
+        let mut game = GameState::new(10, 20);
+        let (x, y) = (game.current_piece.x, game.current_piece.y);
+        assert!(game.apply_batch(Vec::new()).is_empty());
+        assert_eq!(game.ticks, 0);
+        assert_eq!((game.current_piece.x, game.current_piece.y), (x, y));
+        assert!(game.lock_results.is_none());
+    }
+
+    #[test]
+    fn a_batch_returns_its_locks_in_order() {
+        let mut game = well_game(2);
+        let results = game.apply_batch(vec![(0.0, Action::HardDrop), (0.5, Action::HardDrop)]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].score, scoring::line_clear_score(game.settings.scoring_mode, 2, 1));
+        assert_eq!(results[1].score, 0);
+        assert_eq!(game.sim_time(), 0.5);
+    }
+
+    // A T pointing down into a slot under an overhang, as if just turned in:
+    // locking it is a T-spin double
+    fn t_spin_game() -> GameState {
+        let mut game = GameState::new(10, 20);
+        for x in 0..10 {
+            if x != 4 {
+                game.board.fill_garbage(x, 19);
+            }
+            if !(3..6).contains(&x) {
+                game.board.fill_garbage(x, 18);
+            }
+        }
+        game.board.fill_garbage(3, 17);
+        let mut piece = GameState::spawn_new_piece(PieceType::T, 10);
+        piece.shape = GameState::rotate_shape(&GameState::rotate_shape(&piece.shape));
+        piece.rotation = 2;
+        piece.x = 3;
+        piece.y = 18;
+        game.current_piece = piece;
+        game.last_move_was_rotation = true;
+        game.last_rotation_state = 2;
+        game
+    }
+
+    #[test]
+    fn spin_practice_scores_only_spins() {
+        let mut game = t_spin_game();
+        game.settings.spin_practice = true;
+        assert!(game.detect_spin() == Some(PieceType::T));
+        game.lock_piece();
+        let mode = game.settings.scoring_mode;
+        assert_eq!(game.score, scoring::line_clear_score(mode, 2, 1) + scoring::spin_bonus(1));
+
+        let mut game = well_game(2);
+        game.settings.spin_practice = true;
+        let results = game.apply_batch(vec![(0.0, Action::HardDrop)]);
+        assert_eq!(game.stats.lines_cleared, 2);
+        assert_eq!(results[0].score, 0);
+    }
+
+    #[test]
+    fn piece_gravity_scales_the_fall_interval() {
+        let mut game = GameState::new(10, 20);
+        game.piece_gravity[PieceType::I.index()] = 2.0;
+        game.current_piece = GameState::spawn_new_piece(PieceType::I, 10);
+        assert_eq!(game.effective_fall_speed(), game.level_fall_interval() * 2.0);
+        game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+        assert_eq!(game.effective_fall_speed(), game.level_fall_interval());
+    }
+
+    #[test]
+    fn hardcore_hides_the_preview_and_turns_off_hold() {
+        let mut game = GameState::new(10, 20);
+        assert_eq!(game.visible_preview().len(), game.settings.preview_count);
+
+        game.set_mode(GameMode::Hardcore);
+        assert!(game.visible_preview().is_empty());
+        let piece_type = game.current_piece.piece_type;
+        game.apply_actions(&[Action::Hold]);
+        assert!(game.hold.is_none());
+        assert!(game.current_piece.piece_type == piece_type);
+    }
+
+    #[test]
+    fn the_longest_combo_outlasts_a_broken_one() {
+        let mut game = well_game(4);
+        game.import_piece_sequence(&[PieceType::O, PieceType::O, PieceType::O]);
+        game.hard_drop();
+        game.hard_drop();
+        assert_eq!(game.combo, 2);
+        game.hard_drop();
+        assert_eq!(game.combo, 0);
+        assert_eq!(game.stats.max_combo, 2);
+    }
+
+    #[test]
+    fn display_shape_draws_the_spawn_shape() {
+        for piece_type in PieceType::ALL {
+            let drawn: Vec<Vec<bool>> =
+                piece_type.display_shape().lines().map(|line| line.chars().map(|c| c == '█').collect()).collect();
+            assert!(drawn == GameState::get_piece_shape(piece_type), "{}", piece_type.letter());
+        }
+    }
+
+    // Ticks until the current piece locks with soft drop held throughout
+    fn soft_drop_ticks_to_lock(grace: f64) -> u32 {
+        let mut game = GameState::new(10, 20);
+        game.settings.lock_delay = 0.0;
+        game.settings.soft_drop_grace = grace;
+        game.current_piece = GameState::spawn_new_piece(PieceType::O, 10);
+        game.current_piece.y = 17;
+        (1..=60)
+            .find(|_| {
+                game.apply_actions(&[Action::SoftDrop]);
+                game.tick();
+                game.stats.pieces_placed > 0
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn soft_drop_grace_holds_off_the_lock() {
+        let grace = 0.05;
+        let without = soft_drop_ticks_to_lock(0.0);
+        let with = soft_drop_ticks_to_lock(grace);
+        // Give or take the tick the deadline falls on
+        let ticks = (grace / TICK).round() as u32;
+        assert!((ticks..=ticks + 1).contains(&(with - without)), "{} then {} ticks", without, with);
+    }
+
+    #[test]
+    fn survival_clock_runs_down_and_clears_add_time() {
+        let mut game = well_game(2);
+        game.mode = GameMode::SurvivalClock;
+        game.clock_left = 1.0;
+        game.hard_drop();
+        assert_eq!(game.clock_left, 1.0 + 2.0 * game.clock_bonus);
+
+        game.clock_left = 2.5 * TICK;
+        game.tick();
+        game.tick();
+        assert!(!game.game_over);
+        game.tick();
+        assert!(game.game_over);
+    }
+
+    #[test]
+    fn survival_clock_makes_room_instead_of_topping_out() {
+        let mut game = GameState::new(10, 20);
+        game.mode = GameMode::SurvivalClock;
+        for y in 0..19 {
+            game.board.fill_garbage(4, y);
+        }
+        game.spawn_next_piece();
+        assert!(!game.game_over);
+        assert!(game.can_move(game.current_piece.x, game.current_piece.y));
+    }
+
+    #[test]
+    fn a_dig_pattern_decides_the_garbage_gaps() {
+        let mut game = GameState::new(10, 20);
+        game.dig_pattern = Some(DigPattern::parse("2 7", 10).unwrap());
+        game.add_random_garbage(3, 0.5);
+        for (y, gap) in [(17, 2), (18, 7), (19, 2)] {
+            let empty: Vec<usize> = (0..10).filter(|&x| !game.board.grid[y][x].is_filled()).collect();
+            assert_eq!(empty, [gap]);
+        }
+    }
+
+    #[test]
+    fn a_saved_game_of_another_board_size_is_refused() {
+        let path = std::env::temp_dir().join(format!("tetrust_state_test_{}.json", std::process::id()));
+        std::fs::write(&path, GameState::new(10, 16).to_json()).unwrap();
+        let mut config = GameConfig { state: Some(path.to_string_lossy().into_owned()), ..GameConfig::default() };
+        config.grid_dimensions = (10, 20);
+        let refused = load_saved_state(&config);
+        config.grid_dimensions = (10, 16);
+        let loaded = load_saved_state(&config);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(refused, Err(TetrusError::Size(_))));
+        assert!(matches!(loaded, Ok(Some(_))));
+    }
+
+    #[test]
+    fn the_collapse_key_only_works_with_the_debug_overlay() {
+        let mut game = GameState::new(10, 20);
+        game.board.fill_garbage(0, 17);
+        game.apply_actions(&[Action::DebugCollapse]);
+        assert!(game.board.grid[17][0].is_filled());
+
+        game.apply_actions(&[Action::ToggleDebug, Action::DebugCollapse]);
+        assert!(game.board.grid[19][0].is_filled());
+        assert!(!game.board.grid[17][0].is_filled());
+    }
+
+    #[test]
+    fn the_garbage_key_only_works_with_the_debug_overlay() {
+        let mut game = GameState::new(10, 20);
+        game.apply_actions(&[Action::DebugGarbage]);
+        assert_eq!(game.board.max_stack_height(), 0);
+
+        game.apply_actions(&[Action::ToggleDebug, Action::DebugGarbage]);
+        assert_eq!(game.board.max_stack_height(), 1);
+    }
+}
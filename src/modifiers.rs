@@ -0,0 +1,61 @@
+use ::rand::rngs::StdRng;
+use ::rand::seq::index::sample;
+use ::rand::SeedableRng;
+
+// Rules mystery mode turns on at once
+const MYSTERY_RULE_COUNT: usize = 2;
+
+// Rule changes a game can be played under. Ultra hard always uses the same
+// ones; mystery mode draws a few at random.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Modifiers {
+    pub invisible: bool,  // Stack and piece only show on the frame a piece spawns
+    pub twenty_g: bool,  // Pieces drop straight onto the stack
+    pub fast_gravity: bool,  // Gravity at double speed
+    pub mirror: bool,  // Board flips left to right every few pieces
+    pub cheese: bool,  // Starts on rows of one-hole garbage
+}
+
+impl Modifiers {
+    // Same seed, same rules
+    pub fn roll(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut modifiers = Self::default();
+        let flags = modifiers.flags_mut();
+        for i in sample(&mut rng, flags.len(), MYSTERY_RULE_COUNT) {
+            *flags[i].1 = true;
+        }
+        modifiers
+    }
+
+    fn flags_mut(&mut self) -> [(&'static str, &mut bool); 5] {
+        [
+            ("INVISIBLE", &mut self.invisible),
+            ("20G", &mut self.twenty_g),
+            ("FAST GRAVITY", &mut self.fast_gravity),
+            ("MIRROR", &mut self.mirror),
+            ("CHEESE", &mut self.cheese),
+        ]
+    }
+
+    // Names of the rules that are on, for announcing them
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut modifiers = *self;
+        modifiers.flags_mut().into_iter().filter(|(_, on)| **on).map(|(name, _)| name).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seed_always_rolls_the_same_rules() {
+        for seed in 0..20 {
+            let modifiers = Modifiers::roll(seed);
+            assert!(modifiers == Modifiers::roll(seed));
+            assert_eq!(modifiers.names().len(), MYSTERY_RULE_COUNT);
+        }
+        assert!((0..20).any(|seed| Modifiers::roll(seed) != Modifiers::roll(0)));
+    }
+}
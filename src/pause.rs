@@ -0,0 +1,106 @@
+use macroquad::prelude::*;
+
+use crate::input::{Action, KeyBindings};
+use crate::render;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PauseState {
+    Running,
+    Paused,
+    ConfirmQuit,  // Quit pressed once; a second press leaves the game
+}
+
+// What the game loop should do after a menu key
+pub enum MenuCommand {
+    Restart,
+    Quit,
+}
+
+// Pause, restart and quit. No single key press leaves a game in progress:
+// quitting takes the pause menu and a second press to confirm, and restarting
+// works only from the pause menu or once the game is over.
+pub struct PauseMenu {
+    state: PauseState,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self { state: PauseState::Running }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state != PauseState::Running
+    }
+
+    // Steps the menu for `action`; anything but the menu actions is ignored
+    pub fn handle(&mut self, action: Action, game_over: bool) -> Option<MenuCommand> {
+        match (self.state, action) {
+            (PauseState::Running, Action::Pause) => self.state = PauseState::Paused,
+            // Pause again resumes, or backs out of the quit prompt
+            (_, Action::Pause) => self.state = PauseState::Running,
+            (PauseState::ConfirmQuit, Action::Quit) => return Some(MenuCommand::Quit),
+            (PauseState::Paused, Action::Quit) => self.state = PauseState::ConfirmQuit,
+            (PauseState::Running, Action::Quit) if game_over => self.state = PauseState::ConfirmQuit,
+            (PauseState::Running, Action::Restart) if !game_over => {}
+            (_, Action::Restart) => {
+                self.state = PauseState::Running;
+                return Some(MenuCommand::Restart);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw(&self, bindings: &KeyBindings, x: f32, y: f32, dim: f32) {
+        if !self.is_open() {
+            return;
+        }
+        render::draw_dim_overlay(Rect::new(0.0, 0.0, screen_width(), screen_height()), dim);
+        let key = |action| bindings.key_name(action);
+        if self.state == PauseState::ConfirmQuit {
+            draw_text("QUIT?", x, y, 32.0, WHITE);
+            draw_text(&format!("{} again to quit", key(Action::Quit)), x, y + 30.0, 18.0, LIGHTGRAY);
+            draw_text(&format!("{} to go back", key(Action::Pause)), x, y + 54.0, 18.0, LIGHTGRAY);
+            return;
+        }
+        draw_text("PAUSED", x, y, 32.0, WHITE);
+        draw_text(&format!("{}: resume", key(Action::Pause)), x, y + 30.0, 18.0, LIGHTGRAY);
+        draw_text(&format!("{}: restart", key(Action::Restart)), x, y + 54.0, 18.0, LIGHTGRAY);
+        draw_text(&format!("{}: quit", key(Action::Quit)), x, y + 78.0, 18.0, LIGHTGRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quit_during_play_needs_the_menu_and_a_confirm() {
+        let mut menu = PauseMenu::new();
+        // A stray Quit press mid-game does nothing
+        assert!(menu.handle(Action::Quit, false).is_none());
+        assert!(!menu.is_open());
+
+        // From the pause menu, the first Quit only asks
+        menu.handle(Action::Pause, false);
+        assert!(menu.handle(Action::Quit, false).is_none());
+        assert!(matches!(menu.handle(Action::Quit, false), Some(MenuCommand::Quit)));
+    }
+
+    #[test]
+    fn pause_backs_out_of_the_quit_prompt() {
+        let mut menu = PauseMenu::new();
+        menu.handle(Action::Pause, false);
+        menu.handle(Action::Quit, false);
+        menu.handle(Action::Pause, false);
+        assert!(!menu.is_open());
+        assert!(menu.handle(Action::Quit, false).is_none());
+    }
+
+    #[test]
+    fn restart_is_ignored_during_play() {
+        let mut menu = PauseMenu::new();
+        assert!(menu.handle(Action::Restart, false).is_none());
+        assert!(matches!(menu.handle(Action::Restart, true), Some(MenuCommand::Restart)));
+    }
+}
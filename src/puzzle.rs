@@ -0,0 +1,49 @@
+use crate::board::{Board, Cell, GRID_COLS, GRID_ROWS};
+use crate::PieceType;
+use crate::PieceType::*;
+
+// A preset position and the exact pieces to solve it with. The puzzle is
+// decided once the last piece locks.
+pub struct Puzzle {
+    pub goal: &'static str,
+    // Bottom rows of the board, top to bottom: `#` for a filled cell, `.` for empty
+    rows: &'static [&'static str],
+    pub pieces: &'static [PieceType],
+}
+
+// Built-in puzzles, numbered from 1 on the command line
+pub const PUZZLES: [Puzzle; 1] = [
+    Puzzle {
+        goal: "Clear the board in 3 pieces",
+        rows: &[
+            "##......##",
+            "##......##",
+        ],
+        pieces: &[I, O, I],
+    },
+];
+
+impl Puzzle {
+    // `number` counts from 1
+    pub fn find(number: usize) -> Option<&'static Puzzle> {
+        PUZZLES.get(number.checked_sub(1)?)
+    }
+
+    pub fn board(&self) -> Board {
+        let mut board = Board::new(GRID_COLS, GRID_ROWS);
+        let top = GRID_ROWS - self.rows.len();
+        for (dy, row) in self.rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    board.fill_garbage(x, top + dy);
+                }
+            }
+        }
+        board
+    }
+
+    // Every built-in goal so far is clearing the board
+    pub fn is_solved(&self, board: &Board) -> bool {
+        board.grid.iter().flatten().all(|&cell| cell == Cell::Empty)
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Best results for every mode, in one file
+const RECORDS_PATH: &str = "tetrust_records.txt";
+
+// A mode's best results so far
+#[derive(Clone, Copy, Default)]
+pub struct ModeRecord {
+    pub score: u32,
+    pub time: Option<f64>,  // Fastest win in seconds, for modes that can be won
+    pub max_combo: u32,  // Longest run of consecutive clearing pieces
+}
+
+// Records keyed by mode name. The file is a `[mode]` header per mode followed
+// by `key=value` lines; unknown keys and lines that don't parse are skipped,
+// and missing keys keep their defaults, so old files still load as modes and
+// fields are added.
+#[derive(Default)]
+pub struct Records {
+    modes: BTreeMap<String, ModeRecord>,
+}
+
+impl Records {
+    pub fn parse(text: &str) -> Self {
+        let mut records = Self::default();
+        let mut current = None;
+        for line in text.lines().map(str::trim) {
+            if let Some(mode) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                records.modes.entry(mode.to_string()).or_default();
+                current = Some(mode.to_string());
+                continue;
+            }
+
+            let Some(record) = current.as_ref().and_then(|mode| records.modes.get_mut(mode)) else {
+                continue;
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "score" => {
+                    if let Ok(score) = value.trim().parse() {
+                        record.score = score;
+                    }
+                }
+                "time" => {
+                    if let Ok(time) = value.trim().parse() {
+                        record.time = Some(time);
+                    }
+                }
+                "max_combo" => {
+                    if let Ok(combo) = value.trim().parse() {
+                        record.max_combo = combo;
+                    }
+                }
+                _ => {}
+            }
+        }
+        records
+    }
+
+    // No records yet if the file is missing or unreadable
+    pub fn load() -> Self {
+        match std::fs::read_to_string(RECORDS_PATH) {
+            Ok(text) => Self::parse(&text),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("Couldn't read {}: {}", RECORDS_PATH, err);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (mode, record) in &self.modes {
+            let _ = writeln!(text, "[{}]", mode);
+            let _ = writeln!(text, "score={}", record.score);
+            if let Some(time) = record.time {
+                let _ = writeln!(text, "time={:.2}", time);
+            }
+            let _ = writeln!(text, "max_combo={}", record.max_combo);
+        }
+        text
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::write(RECORDS_PATH, self.to_text())
+    }
+
+    pub fn get(&self, mode: &str) -> Option<ModeRecord> {
+        self.modes.get(mode).copied()
+    }
+
+    // Folds a finished game into `mode`'s record. Returns true if it beat the
+    // score or, for a win, the time.
+    pub fn submit(&mut self, mode: &str, score: u32, win_time: Option<f64>) -> bool {
+        let record = self.modes.entry(mode.to_string()).or_default();
+        let mut improved = false;
+        if score > record.score {
+            record.score = score;
+            improved = true;
+        }
+        if let Some(time) = win_time {
+            if record.time.is_none_or(|best| time < best) {
+                record.time = Some(time);
+                improved = true;
+            }
+        }
+        improved
+    }
+
+    // Kept apart from `submit` since a longer combo alone isn't a new record
+    // for the mode. Returns true if `combo` beat the best.
+    pub fn submit_combo(&mut self, mode: &str, combo: u32) -> bool {
+        let record = self.modes.entry(mode.to_string()).or_default();
+        if combo > record.max_combo {
+            record.max_combo = combo;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_keys_and_stray_lines_are_skipped() {
+        let records = Records::parse(
+            "score=5\n\
+             [marathon]\n\
+             score=1200\n\
+             rating=9000\n\
+             not a field\n\
+             time=oops\n\
+             [sprint]\n\
+             time=61.5\n",
+        );
+        let marathon = records.get("marathon").unwrap();
+        assert_eq!(marathon.score, 1200);
+        assert!(marathon.time.is_none());
+        assert_eq!(marathon.max_combo, 0);
+        assert_eq!(records.get("sprint").unwrap().time, Some(61.5));
+        assert!(records.get("zen").is_none());
+    }
+
+    #[test]
+    fn records_are_kept_per_mode_through_a_save() {
+        let mut records = Records::default();
+        assert!(records.submit("marathon", 900, None));
+        assert!(!records.submit("marathon", 800, None));
+        assert!(records.submit("sprint", 0, Some(70.0)));
+        assert!(records.submit("sprint", 0, Some(65.25)));
+
+        let loaded = Records::parse(&records.to_text());
+        assert_eq!(loaded.get("marathon").unwrap().score, 900);
+        assert_eq!(loaded.get("sprint").unwrap().time, Some(65.25));
+    }
+
+    #[test]
+    fn the_best_combo_is_kept_apart_from_the_score() {
+        let mut records = Records::default();
+        assert!(records.submit_combo("marathon", 6));
+        assert!(!records.submit_combo("marathon", 4));
+        assert_eq!(records.get("marathon").unwrap().score, 0);
+
+        let loaded = Records::parse(&records.to_text());
+        assert_eq!(loaded.get("marathon").unwrap().max_combo, 6);
+    }
+}
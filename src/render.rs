@@ -0,0 +1,1046 @@
+use macroquad::prelude::*;
+
+use crate::grade::ChallengeScore;
+use crate::input::{Action, KeyBindings};
+use crate::puzzle::Puzzle;
+use crate::tetris_animation::AnimPhase;
+use crate::{GameMode, GameState, GravityDirection, PieceType, DROP_TRAIL_DURATION, MAX_SPEED_MULTIPLIER, MYSTERY_INTRO, PENALTY_ANIM_DURATION, POPUP_DURATION, SACRIFICE_PENALTY, VISIBLE_ROWS};
+
+// Width of the info panel to the right of the playfield, in blocks
+const SIDE_PANEL_BLOCKS: f32 = 6.0;
+// Cell size of the NEXT queue icons, in pixels
+const PREVIEW_CELL: f32 = 12.0;
+// Cell size used instead when the queue is long enough to need two columns
+const PREVIEW_CELL_SMALL: f32 = 9.0;
+// Cell size of the next-bag preview icons, in pixels
+const BAG_PREVIEW_CELL: f32 = 8.0;
+// Seconds for a locked block to fade to its oldest look under settings.age_colors
+const AGE_FADE_SECONDS: f64 = 60.0;
+// Height of an isometric block's side faces, as a fraction of the block size
+const ISO_BLOCK_HEIGHT: f32 = 0.5;
+// Seconds past the zen limit until the red vignette is at full strength
+const ZEN_VIGNETTE_RAMP: f64 = 30.0;
+// Largest cell size used by the tall-board minimap, in pixels
+const MINIMAP_MAX_CELL: f32 = 6.0;
+
+// Screen layout derived from the board size
+pub struct Layout {
+    block_size: f32,
+    view_cols: usize,  // Playfield width including the border, in blocks
+    view_rows: usize,  // Playfield height including the border, in blocks
+    gravity: GravityDirection,  // Right turns the playfield on its side on screen
+}
+
+impl Layout {
+    pub fn new(game_state: &GameState) -> Self {
+        Self {
+            block_size: game_state.block_size,
+            view_cols: game_state.board.width() + 2,
+            view_rows: game_state.board.height().min(VISIBLE_ROWS) + 2,
+            gravity: game_state.mode.gravity_direction(),
+        }
+    }
+
+    // Playfield width and height as they appear on screen, in blocks
+    fn screen_blocks(&self) -> (usize, usize) {
+        match self.gravity {
+            GravityDirection::Down => (self.view_cols, self.view_rows),
+            GravityDirection::Right => (self.view_rows, self.view_cols),
+        }
+    }
+
+    pub fn window_size(&self) -> (f32, f32) {
+        let (cols, rows) = self.screen_blocks();
+        (
+            self.block_size * (cols as f32 + SIDE_PANEL_BLOCKS),
+            self.block_size * rows as f32,
+        )
+    }
+
+    pub fn playfield_width(&self) -> f32 {
+        self.block_size * self.view_cols as f32
+    }
+
+    fn playfield_height(&self) -> f32 {
+        self.block_size * self.view_rows as f32
+    }
+
+    fn panel_x(&self) -> f32 {
+        self.block_size * (self.screen_blocks().0 as f32 + 0.5)
+    }
+}
+
+// Everything inside the playfield, drawn the right way up for the board
+fn draw_playfield(game_state: &GameState, layout: &Layout) {
+    draw_border(game_state, layout);
+    draw_garbage_meter(game_state, layout);
+    draw_training_ghost(game_state);
+    draw_board(game_state);
+    draw_drop_trails(game_state);
+    draw_ghost_piece(game_state);
+    draw_motion_trail(game_state);
+    draw_current_piece(game_state);
+    draw_column_markers(game_state);
+    draw_zen_vignette(game_state, layout);
+    draw_desperation_hint(game_state);
+}
+
+// Darkens `area` so text drawn over it stands out. Used behind pause screens
+// and the game over results, with `settings.overlay_dim` as `alpha`.
+pub fn draw_dim_overlay(area: Rect, alpha: f32) {
+    draw_rectangle(area.x, area.y, area.w, area.h, Color::new(0.0, 0.0, 0.0, alpha));
+}
+
+// Stretches `texture` dimly over the whole window, behind everything else
+pub fn draw_background(texture: &Texture2D) {
+    draw_texture_ex(
+        texture,
+        0.0,
+        0.0,
+        Color::new(1.0, 1.0, 1.0, 0.3),
+        DrawTextureParams {
+            dest_size: Some(vec2(screen_width(), screen_height())),
+            ..Default::default()
+        }
+    );
+}
+
+// Shifts everything drawn until `set_default_camera` right by `x`
+fn set_offset_camera(x: f32) {
+    set_camera(&Camera2D::from_display_rect(Rect::new(-x, 0.0, screen_width(), screen_height())));
+}
+
+// Screen-space camera that shifts everything right by `shift_x` and turns it
+// `degrees` clockwise around `pivot`
+#[derive(Clone, Copy)]
+struct TiltCamera {
+    shift_x: f32,
+    pivot: Vec2,
+    degrees: f32,
+}
+
+impl Camera for TiltCamera {
+    fn matrix(&self) -> Mat4 {
+        Mat4::orthographic_rh_gl(0.0, screen_width(), screen_height(), 0.0, -1.0, 1.0)
+            * Mat4::from_translation(vec3(self.pivot.x + self.shift_x, self.pivot.y, 0.0))
+            * Mat4::from_rotation_z(self.degrees.to_radians())
+            * Mat4::from_translation(vec3(-self.pivot.x, -self.pivot.y, 0.0))
+    }
+
+    fn depth_enabled(&self) -> bool {
+        false
+    }
+
+    fn render_pass(&self) -> Option<RenderPass> {
+        None
+    }
+
+    fn viewport(&self) -> Option<(i32, i32, i32, i32)> {
+        None
+    }
+}
+
+// How far the view leans toward the current piece: none with it centered,
+// the full `settings.board_tilt_degrees` with it against a wall
+fn board_tilt(game_state: &GameState) -> f32 {
+    if !game_state.settings.dynamic_tilt {
+        return 0.0;
+    }
+    let piece = &game_state.current_piece;
+    let half_width = game_state.board.width() as f32 / 2.0;
+    let piece_center = piece.x as f32 + piece.shape[0].len() as f32 / 2.0;
+    let lean = ((piece_center - half_width) / half_width).clamp(-1.0, 1.0);
+    game_state.settings.board_tilt_degrees * lean
+}
+
+// Draws a whole game, side panel included, shifted right by `x` and tilted
+// around the playfield's center under `settings.dynamic_tilt`. With gravity
+// to the right the playfield and its overlays are turned a quarter anticlockwise,
+// its top on the left, while the side panel stays upright.
+pub fn draw_game_at(game_state: &GameState, layout: &Layout, x: f32) {
+    let (playfield, panel) = match layout.gravity {
+        GravityDirection::Down => {
+            let camera = TiltCamera {
+                shift_x: x,
+                pivot: vec2(layout.playfield_width() / 2.0, layout.playfield_height() / 2.0),
+                degrees: board_tilt(game_state),
+            };
+            (camera, camera)
+        }
+        GravityDirection::Right => {
+            // Turning around a point as far from the top and left edges as
+            // the playfield is wide lands it back at the top-left corner
+            let half_width = layout.playfield_width() / 2.0;
+            (
+                TiltCamera { shift_x: x, pivot: vec2(half_width, half_width), degrees: -90.0 },
+                TiltCamera { shift_x: x, pivot: Vec2::ZERO, degrees: 0.0 },
+            )
+        }
+    };
+
+    set_camera(&playfield);
+    draw_playfield(game_state, layout);
+    set_camera(&panel);
+    draw_side_panel(game_state, layout);
+    set_camera(&playfield);
+    draw_overlays(game_state, layout);
+    set_default_camera();
+}
+
+// Draws an opponent's playfield only, shifted right by `x`, with `label`
+// above it
+pub fn draw_opponent(game_state: &GameState, layout: &Layout, x: f32, label: &str) {
+    set_offset_camera(x);
+    draw_border(game_state, layout);
+    draw_board(game_state);
+    draw_current_piece(game_state);
+    draw_text(label, layout.block_size, layout.block_size * 0.75, 20.0, WHITE);
+    if game_state.game_over {
+        draw_centered_text(layout, "TOPPED OUT", layout.playfield_height() / 2.0, 32.0, WHITE);
+    }
+    set_default_camera();
+}
+
+// Draws a small grid of cells with its top-left corner at (x, y). Used for
+// anything drawn outside the playfield at a reduced size.
+pub fn draw_mini_shape(cells: &[Vec<Option<Color>>], x: f32, y: f32, cell_size: f32) {
+    draw_mini_shape_squeezed(cells, x, y, cell_size, 1.0);
+}
+
+// `draw_mini_shape` with the cells narrowed to `scale_x` of their width,
+// about the shape's middle
+fn draw_mini_shape_squeezed(cells: &[Vec<Option<Color>>], x: f32, y: f32, cell_size: f32, scale_x: f32) {
+    let width = cells.first().map_or(0, |row| row.len()) as f32 * cell_size;
+    let cell_width = cell_size * scale_x;
+    let left = x + width * (1.0 - scale_x) / 2.0;
+    for (row_idx, row) in cells.iter().enumerate() {
+        for (col_idx, cell) in row.iter().enumerate() {
+            if let Some(color) = cell {
+                draw_rectangle(
+                    left + col_idx as f32 * cell_width,
+                    y + row_idx as f32 * cell_size,
+                    cell_width,
+                    cell_size,
+                    *color
+                );
+            }
+        }
+    }
+}
+
+// Cells for drawing a piece type's spawn shape with `draw_mini_shape`
+pub fn mini_piece_cells(piece_type: PieceType, color: Color) -> Vec<Vec<Option<Color>>> {
+    GameState::get_piece_shape(piece_type)
+        .iter()
+        .map(|row| row.iter().map(|&cell| cell.then_some(color)).collect())
+        .collect()
+}
+
+// Draws one board cell, offset for the border and scrolled view. Cells
+// outside the view are skipped.
+fn draw_cell(game_state: &GameState, x: i32, y: i32, color: Color) {
+    let row = y - game_state.view_top() as i32;
+    if row < 0 || row >= VISIBLE_ROWS as i32 {
+        return;
+    }
+    draw_rectangle(
+        (x + 1) as f32 * game_state.block_size,
+        (row + 1) as f32 * game_state.block_size,
+        game_state.block_size - 1.0,
+        game_state.block_size - 1.0,
+        color
+    );
+}
+
+// Scales a color's brightness by `factor`, keeping its alpha
+fn shade(color: Color, factor: f32) -> Color {
+    Color::new(color.r * factor, color.g * factor, color.b * factor, color.a)
+}
+
+// `color` washed out toward gray and dimmed the longer ago its block locked
+fn aged_color(color: Color, age: f64) -> Color {
+    let t = (age / AGE_FADE_SECONDS).clamp(0.0, 1.0) as f32;
+    let gray = (color.r + color.g + color.b) / 3.0;
+    let fade = |channel: f32| (channel + (gray - channel) * t * 0.8) * (1.0 - t * 0.3);
+    Color::new(fade(color.r), fade(color.g), fade(color.b), color.a)
+}
+
+// The color a locked board cell is drawn in, aged if settings.age_colors is on
+fn board_cell_color(game_state: &GameState, x: usize, y: usize, color: Color) -> Color {
+    if !game_state.settings.age_colors {
+        return color;
+    }
+    match game_state.board.placed_at(x, y) {
+        Some(placed) => aged_color(color, game_state.sim_time() - placed),
+        None => color,
+    }
+}
+
+// Corners of the isometric block in view cell (col, row): the top of its top
+// face, the outer top corners and center of the top face, the outer bottom
+// corners and the bottom. `z_height` is how tall the sides stand, in blocks.
+fn isometric_corners(col: i32, row: i32, z_height: f32, block_size: f32) -> [Vec2; 7] {
+    let x = (col + 1) as f32 * block_size;
+    let y = (row + 1) as f32 * block_size;
+    let top = block_size * (1.0 - z_height.clamp(0.0, 1.0));
+    let face = top / 2.0;
+    let cx = x + block_size / 2.0;
+    [
+        vec2(cx, y),
+        vec2(x, y + face),
+        vec2(x + block_size, y + face),
+        vec2(cx, y + top),
+        vec2(x, y + block_size - face),
+        vec2(x + block_size, y + block_size - face),
+        vec2(cx, y + block_size),
+    ]
+}
+
+// Draws a block as a little isometric cube filling view cell (col, row): a
+// top face in `color` over a left side 30% darker and a right side 20% darker
+fn draw_block_isometric(col: i32, row: i32, z_height: f32, color: Color, block_size: f32) {
+    let [peak, left, right, center, low_left, low_right, bottom] =
+        isometric_corners(col, row, z_height, block_size);
+    let top = color;
+    draw_triangle(peak, left, center, top);
+    draw_triangle(peak, right, center, top);
+    let left_side = shade(color, 0.7);
+    draw_triangle(left, center, bottom, left_side);
+    draw_triangle(left, low_left, bottom, left_side);
+    let right_side = shade(color, 0.8);
+    draw_triangle(right, center, bottom, right_side);
+    draw_triangle(right, low_right, bottom, right_side);
+}
+
+// Shades the top face of the first block under each column of the current
+// piece, as if lit from straight above
+fn draw_piece_shadow(game_state: &GameState) {
+    let piece = &game_state.current_piece;
+    let view_top = game_state.view_top() as i32;
+    for col in 0..piece.shape[0].len() {
+        let Some(bottom) = (0..piece.shape.len()).rev().find(|&row| piece.shape[row][col]) else {
+            continue;
+        };
+        let x = piece.x + col as i32;
+        let below = (piece.y + bottom as i32 + 1).max(0) as usize;
+        let Some(y) = (below..game_state.board.height()).find(|&y| game_state.board.grid[y][x as usize].is_filled()) else {
+            continue;
+        };
+        let row = y as i32 - view_top;
+        if (0..VISIBLE_ROWS as i32).contains(&row) {
+            let [peak, left, right, center, ..] = isometric_corners(x, row, ISO_BLOCK_HEIGHT, game_state.block_size);
+            let shadow = Color::new(0.0, 0.0, 0.0, 0.4);
+            draw_triangle(peak, left, center, shadow);
+            draw_triangle(peak, right, center, shadow);
+        }
+    }
+}
+
+// Draws `text` centered over the playfield with its baseline at `y`
+fn draw_centered_text(layout: &Layout, text: &str, y: f32, font_size: f32, color: Color) {
+    let size = measure_text(text, None, font_size as u16, 1.0);
+    draw_text(text, (layout.playfield_width() - size.width) / 2.0, y, font_size, color);
+}
+
+fn draw_border(game_state: &GameState, layout: &Layout) {
+    let border_color = DARKGRAY;
+    for y in 0..layout.view_rows {
+        for x in 0..layout.view_cols {
+            if y == 0 || y == layout.view_rows - 1 || x == 0 || x == layout.view_cols - 1 {
+                draw_rectangle(
+                    x as f32 * game_state.block_size,
+                    y as f32 * game_state.block_size,
+                    game_state.block_size,
+                    game_state.block_size,
+                    border_color
+                );
+            }
+        }
+    }
+}
+
+// Small arrows in the top border over every column the current piece
+// covers, for counting columns at a glance
+fn draw_column_markers(game_state: &GameState) {
+    if !game_state.settings.column_markers || game_state.game_over || !cells_visible(game_state) {
+        return;
+    }
+    let piece = &game_state.current_piece;
+    let size = game_state.block_size;
+    let color = piece.piece_type.get_color();
+    for col in 0..piece.shape[0].len() {
+        if !piece.shape.iter().any(|row| row[col]) {
+            continue;
+        }
+        let left = (piece.x + col as i32 + 1) as f32 * size;
+        draw_triangle(
+            vec2(left + size * 0.25, size * 0.4),
+            vec2(left + size * 0.75, size * 0.4),
+            vec2(left + size * 0.5, size * 0.85),
+            color
+        );
+    }
+}
+
+// The invisible modifier hides the board and piece except on the frame a piece spawns
+fn cells_visible(game_state: &GameState) -> bool {
+    !game_state.modifiers.invisible || game_state.fresh_spawn || game_state.game_over
+}
+
+fn draw_board(game_state: &GameState) {
+    if !cells_visible(game_state) {
+        return;
+    }
+    if game_state.settings.isometric {
+        let view_top = game_state.view_top() as i32;
+        for (y, row) in game_state.board.grid.iter().enumerate() {
+            let view_row = y as i32 - view_top;
+            if !(0..VISIBLE_ROWS as i32).contains(&view_row) {
+                continue;
+            }
+            for (x, cell) in row.iter().enumerate() {
+                if let Some(color) = cell.color() {
+                    let color = board_cell_color(game_state, x, y, color);
+                    draw_block_isometric(x as i32, view_row, ISO_BLOCK_HEIGHT, color, game_state.block_size);
+                }
+            }
+        }
+        if !game_state.game_over {
+            draw_piece_shadow(game_state);
+        }
+        return;
+    }
+
+    for (y, row) in game_state.board.grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if let Some(color) = cell.color() {
+                draw_cell(game_state, x as i32, y as i32, board_cell_color(game_state, x, y, color));
+            }
+        }
+    }
+}
+
+// The recorded game's board, faintly behind the live one
+fn draw_training_ghost(game_state: &GameState) {
+    let Some(ghost) = &game_state.training_ghost else {
+        return;
+    };
+    for (y, row) in ghost.board.grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if let Some(mut color) = cell.color() {
+                color.a = 0.15;
+                draw_cell(game_state, x as i32, y as i32, color);
+            }
+        }
+    }
+}
+
+// Faded copy of the current piece where it would land
+fn draw_ghost_piece(game_state: &GameState) {
+    // No landing hints when playing blind
+    if game_state.modifiers.invisible {
+        return;
+    }
+
+    let piece = &game_state.current_piece;
+    let ghost_y = game_state.drop_projection();
+    let mut color = piece.piece_type.get_color();
+    color.a *= game_state.settings.ghost_alpha;
+    for (dy, row) in piece.shape.iter().enumerate() {
+        for (dx, &cell) in row.iter().enumerate() {
+            if cell {
+                draw_cell(game_state, piece.x + dx as i32, ghost_y + dy as i32, color);
+            }
+        }
+    }
+}
+
+// Fading copies of the current piece along its recent path. The newest
+// frame is where the piece is now and is covered by it.
+fn draw_motion_trail(game_state: &GameState) {
+    if !cells_visible(game_state) {
+        return;
+    }
+    let color = game_state.current_piece.piece_type.get_color();
+    for frame in &game_state.motion_trail {
+        for (dy, row) in frame.shape.iter().enumerate() {
+            for (dx, &cell) in row.iter().enumerate() {
+                if cell {
+                    let faded = Color::new(color.r, color.g, color.b, color.a * frame.opacity);
+                    draw_cell(game_state, frame.x + dx as i32, frame.y + dy as i32, faded);
+                }
+            }
+        }
+    }
+}
+
+fn draw_current_piece(game_state: &GameState) {
+    if !cells_visible(game_state) {
+        return;
+    }
+    let piece = &game_state.current_piece;
+    for (dy, row) in piece.shape.iter().enumerate() {
+        for (dx, &cell) in row.iter().enumerate() {
+            if cell {
+                draw_cell(
+                    game_state,
+                    piece.x + dx as i32,
+                    piece.y + dy as i32,
+                    piece.piece_type.get_color()
+                );
+            }
+        }
+    }
+}
+
+// Incoming garbage stacked up the left border, one block per pending row
+fn draw_garbage_meter(game_state: &GameState, layout: &Layout) {
+    let pending = game_state.pending_garbage_lines();
+    if pending == 0 {
+        return;
+    }
+
+    let rows = (pending as f32).min(layout.view_rows as f32 - 2.0);
+    let bottom = layout.playfield_height() - layout.block_size;
+    let height = rows * layout.block_size;
+    draw_rectangle(layout.block_size * 0.25, bottom - height, layout.block_size * 0.5, height, RED);
+}
+
+fn draw_drop_trails(game_state: &GameState) {
+    let view_top = game_state.view_top() as i32;
+    let block = game_state.block_size;
+    for trail in &game_state.drop_trails {
+        let fade = 1.0 - ((game_state.sim_time() - trail.started_at) / DROP_TRAIL_DURATION) as f32;
+        let color = Color::new(trail.color.r, trail.color.g, trail.color.b, 0.5 * fade.max(0.0));
+        for &(x, top, bottom) in &trail.columns {
+            // Clip to the rows in view
+            let top = (top - view_top).max(0);
+            let bottom = (bottom - view_top).min(VISIBLE_ROWS as i32 - 1);
+            if top > bottom {
+                continue;
+            }
+            draw_rectangle(
+                (x + 1) as f32 * block + block * 0.35,
+                (top + 1) as f32 * block,
+                block * 0.3,
+                (bottom - top + 1) as f32 * block,
+                color
+            );
+        }
+    }
+}
+
+// Red glow creeping in from the playfield edges once the zen timer runs past
+// its limit, growing stronger the longer no line is cleared
+fn draw_zen_vignette(game_state: &GameState, layout: &Layout) {
+    let overtime = game_state.zen_timer - game_state.settings.zen_limit;
+    if overtime <= 0.0 {
+        return;
+    }
+
+    let strength = (overtime / ZEN_VIGNETTE_RAMP).min(1.0) as f32;
+    let (left, top) = (layout.block_size, layout.block_size);
+    let width = layout.playfield_width() - layout.block_size * 2.0;
+    let height = layout.playfield_height() - layout.block_size * 2.0;
+    for i in 0..8 {
+        let inset = i as f32 * 4.0;
+        draw_rectangle_lines(
+            left + inset,
+            top + inset,
+            width - inset * 2.0,
+            height - inset * 2.0,
+            4.0,
+            Color::new(1.0, 0.0, 0.0, strength * 0.6 * (1.0 - i as f32 / 8.0))
+        );
+    }
+}
+
+// Flashes the outline of a placement that would clear a line and the
+// "CLEAR TO SURVIVE" prompt
+fn draw_desperation_hint(game_state: &GameState) {
+    let Some(placement) = &game_state.desperation_hint else {
+        return;
+    };
+
+    let alpha = 0.5 + 0.3 * (get_time() * 6.0).sin() as f32;
+    let view_top = game_state.view_top() as i32;
+    for (dy, row) in placement.shape.iter().enumerate() {
+        for (dx, &cell) in row.iter().enumerate() {
+            let view_row = placement.y + dy as i32 - view_top;
+            if cell && (0..VISIBLE_ROWS as i32).contains(&view_row) {
+                draw_rectangle_lines(
+                    ((placement.x + dx as i32 + 1) as f32) * game_state.block_size,
+                    ((view_row + 1) as f32) * game_state.block_size,
+                    game_state.block_size - 1.0,
+                    game_state.block_size - 1.0,
+                    2.0,
+                    Color::new(1.0, 1.0, 1.0, alpha)
+                );
+            }
+        }
+    }
+    draw_text(
+        "CLEAR TO SURVIVE",
+        game_state.block_size * 1.5,
+        game_state.block_size * 0.75,
+        20.0,
+        Color::new(1.0, 1.0, 1.0, alpha)
+    );
+}
+
+// Panel contents are laid out top to bottom; `y` tracks the next free line
+fn draw_side_panel(game_state: &GameState, layout: &Layout) {
+    let x = layout.panel_x();
+    let mut y = layout.block_size;
+
+    // Held piece, dimmed once this piece has used its hold. A swap flips the
+    // panel over, showing the old piece until it's edge-on.
+    if game_state.mode.allows_hold() {
+        draw_text("HOLD", x, y, 24.0, WHITE);
+        y += 10.0;
+        let (shown, scale_x) = match &game_state.hold_animation {
+            Some(flip) if !flip.swapped() => (flip.outgoing, flip.scale_x),
+            Some(flip) => (game_state.hold, flip.scale_x),
+            None => (game_state.hold, 1.0),
+        };
+        if let Some(held) = shown {
+            let mut color = held.get_color();
+            if game_state.hold_used {
+                color.a = 0.4;
+            }
+            draw_mini_shape_squeezed(&mini_piece_cells(held, color), x, y, PREVIEW_CELL, scale_x);
+        }
+        y += PREVIEW_CELL * 3.0 + layout.block_size * 0.5;
+    }
+
+    // Upcoming pieces; long queues are split over two narrower columns.
+    // Whatever isn't shown is left out and the rest of the panel moves up.
+    let preview = game_state.visible_preview();
+    let count = preview.len();
+    if count > 0 {
+        draw_text("NEXT", x, y, 24.0, WHITE);
+        y += 10.0;
+
+        let (columns, cell) = if count > 7 { (2, PREVIEW_CELL_SMALL) } else { (1, PREVIEW_CELL) };
+        let per_column = count.div_ceil(columns);
+        for (i, piece_type) in preview.iter().enumerate() {
+            draw_mini_shape(
+                &mini_piece_cells(*piece_type, piece_type.get_color()),
+                x + (i / per_column) as f32 * cell * 6.0,
+                y + (i % per_column) as f32 * cell * 3.0,
+                cell
+            );
+        }
+        y += per_column as f32 * cell * 3.0 + layout.block_size * 0.5;
+    }
+
+    // Near the end of a bag, show the order of the whole next bag
+    let shows_bag = game_state.mode.shows_preview()
+        && game_state.sequence.deals_bags()
+        && !game_state.sequence.is_frozen();
+    if shows_bag && game_state.sequence.remaining_in_bag() < 3 {
+        draw_text("NEXT BAG", x, y, 18.0, GRAY);
+        for (i, piece_type) in game_state.sequence.peek_next_bag().iter().enumerate() {
+            let mut color = piece_type.get_color();
+            color.a = 0.5;
+            draw_mini_shape(
+                &mini_piece_cells(*piece_type, color),
+                x + (i % 4) as f32 * BAG_PREVIEW_CELL * 5.0,
+                y + 8.0 + (i / 4) as f32 * BAG_PREVIEW_CELL * 3.0,
+                BAG_PREVIEW_CELL
+            );
+        }
+        y += 8.0 + BAG_PREVIEW_CELL * 6.0 + layout.block_size * 0.5;
+    }
+
+    y += 20.0;
+    draw_text(&format!("SCORE {}", game_state.score), x, y, 20.0, WHITE);
+    if let Some(shown_at) = game_state.penalty_shown_at {
+        let t = (game_state.sim_time() - shown_at) / PENALTY_ANIM_DURATION;
+        if t < 1.0 {
+            draw_text(
+                &format!("-{}", SACRIFICE_PENALTY),
+                x + layout.block_size * 3.0,
+                y - t as f32 * layout.block_size * 0.5,
+                20.0,
+                Color::new(1.0, 0.2, 0.2, 1.0 - t as f32)
+            );
+        }
+    }
+
+    y += 22.0;
+    draw_text(&format!("LEVEL {}", game_state.level()), x, y, 20.0, WHITE);
+    y += 22.0;
+    draw_text(&format!("{} lines to next", game_state.lines_to_next_level()), x, y, 20.0, LIGHTGRAY);
+
+    if game_state.settings.score_projection {
+        y += 22.0;
+        let best = game_state.projected_clear_score();
+        draw_text(&format!("BEST +{}", best), x, y, 20.0, if best > 0 { GREEN } else { GRAY });
+    }
+
+    y += 30.0;
+    draw_text("CLEARS", x, y, 24.0, WHITE);
+    y += 24.0;
+    draw_text(
+        &format!("{}/7 pieces", game_state.stats.pieces_cleared_with()),
+        x,
+        y,
+        20.0,
+        if game_state.stats.all_pieces_cleared { GOLD } else { LIGHTGRAY }
+    );
+    y += 24.0;
+    draw_text(&format!("PPS {:.2}", game_state.stats.pps()), x, y, 20.0, LIGHTGRAY);
+    y += 22.0;
+    draw_text(&format!("APM {:.1}", game_state.stats.apm()), x, y, 20.0, LIGHTGRAY);
+    y += 22.0;
+    let zen_color = if game_state.zen_timer > game_state.settings.zen_limit { RED } else { LIGHTGRAY };
+    draw_text(&format!("ZEN {:.1}s", game_state.zen_timer), x, y, 20.0, zen_color);
+    y += 22.0;
+    let entropy = game_state.stats.surface_entropy;
+    let entropy_color = match entropy {
+        0..=4 => GREEN,
+        5..=15 => YELLOW,
+        _ => RED,
+    };
+    draw_text(&format!("ENTROPY {}", entropy), x, y, 20.0, entropy_color);
+    if let Some(delta) = game_state.pb_pace.as_ref().and_then(|pace| pace.delta(game_state.sim_time())) {
+        y += 22.0;
+        let (label, color) = if delta > 0.0 { ("BEHIND", RED) } else { ("AHEAD", GREEN) };
+        draw_text(&format!("PB {} {:.1}s", label, delta.abs()), x, y, 20.0, color);
+    }
+    y += 22.0;
+    draw_speed_bar(game_state, x, y);
+    if game_state.mode.is_versus() {
+        // Digging out garbage matters more than clearing your own rows
+        let stats = &game_state.stats;
+        y += 22.0;
+        draw_text(
+            &format!("DIG {} / {} lines", stats.garbage_lines_cleared, stats.lines_cleared),
+            x,
+            y,
+            20.0,
+            LIGHTGRAY
+        );
+    }
+    if let Some(rewind) = &game_state.rewind {
+        y += 22.0;
+        let cooldown = rewind.cooldown_left(game_state.sim_time());
+        if cooldown > 0.0 {
+            draw_text(&format!("REWIND {:.0}s", cooldown.ceil()), x, y, 20.0, GRAY);
+        } else {
+            draw_text("REWIND (U)", x, y, 20.0, GREEN);
+        }
+    }
+    y += layout.block_size * 0.5;
+
+    // Tall boards don't fit in the playfield, so show all of it scaled down
+    if game_state.board.height() > VISIBLE_ROWS && cells_visible(game_state) {
+        draw_minimap(game_state, layout, x, y);
+    }
+}
+
+// Fill shows how far the speed multiplier is from 1.0 towards its cap
+fn draw_speed_bar(game_state: &GameState, x: f32, y: f32) {
+    let multiplier = game_state.speed_multiplier;
+    draw_text(&format!("SPEED x{:.1}", multiplier), x, y, 20.0, LIGHTGRAY);
+
+    let (bar_x, bar_width, bar_height) = (x + 100.0, 60.0, 10.0);
+    let fill = (multiplier - 1.0) / (MAX_SPEED_MULTIPLIER - 1.0);
+    draw_rectangle(bar_x, y - bar_height, bar_width, bar_height, DARKGRAY);
+    draw_rectangle(bar_x, y - bar_height, bar_width * fill, bar_height, ORANGE);
+}
+
+fn draw_minimap(game_state: &GameState, layout: &Layout, x: f32, y: f32) {
+    let board = &game_state.board;
+    let cell_size = ((layout.playfield_height() - y - layout.block_size) / board.height() as f32)
+        .min(MINIMAP_MAX_CELL);
+
+    let mut cells: Vec<Vec<Option<Color>>> = board.grid
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.color()).collect())
+        .collect();
+    let piece = &game_state.current_piece;
+    for (dy, row) in piece.shape.iter().enumerate() {
+        for (dx, &cell) in row.iter().enumerate() {
+            let (px, py) = (piece.x + dx as i32, piece.y + dy as i32);
+            if cell && py >= 0 {
+                cells[py as usize][px as usize] = Some(piece.piece_type.get_color());
+            }
+        }
+    }
+
+    let map_width = board.width() as f32 * cell_size;
+    let map_height = board.height() as f32 * cell_size;
+    draw_rectangle(x, y, map_width, map_height, Color::new(0.15, 0.15, 0.15, 1.0));
+    draw_mini_shape(&cells, x, y, cell_size);
+    // Outline the part of the board currently in the playfield
+    draw_rectangle_lines(
+        x,
+        y + game_state.view_top() as f32 * cell_size,
+        map_width,
+        VISIBLE_ROWS as f32 * cell_size,
+        1.0,
+        WHITE
+    );
+}
+
+// Text drawn over the playfield: popups, prompts and the game over screen
+fn draw_overlays(game_state: &GameState, layout: &Layout) {
+    if game_state.game_over {
+        let inside = Rect::new(
+            layout.block_size,
+            layout.block_size,
+            layout.playfield_width() - layout.block_size * 2.0,
+            layout.playfield_height() - layout.block_size * 2.0
+        );
+        draw_dim_overlay(inside, game_state.settings.overlay_dim);
+    }
+
+    if game_state.mode == GameMode::SurvivalClock && !game_state.game_over {
+        let color = if game_state.clock_left < 10.0 { RED } else { WHITE };
+        draw_centered_text(layout, &format!("{:.1}", game_state.clock_left), layout.block_size * 2.5, 48.0, color);
+    }
+
+    if let Some((text, shown_at)) = &game_state.popup {
+        if get_time() - shown_at < POPUP_DURATION {
+            draw_centered_text(layout, text, layout.block_size * 6.0, 24.0, GOLD);
+        }
+    }
+
+    if !game_state.game_over
+        && game_state.mode.allows_sacrifice()
+        && is_key_down(KeyCode::Backspace)
+    {
+        draw_centered_text(
+            layout,
+            "SACRIFICE? Enter to confirm",
+            layout.playfield_height() / 2.0,
+            20.0,
+            RED
+        );
+    }
+
+    draw_tetris_animation(game_state, layout);
+
+    if game_state.isolated_holes && !game_state.game_over {
+        draw_centered_text(layout, "ISOLATED HOLES", layout.playfield_height() - layout.block_size * 1.5, 20.0, ORANGE);
+    }
+
+    if game_state.show_debug {
+        draw_debug_overlay(game_state);
+    }
+
+    if let GameMode::ComboChallenge { required_combo } = game_state.mode {
+        draw_centered_text(
+            layout,
+            &format!("COMBO {}/{}", game_state.combo, required_combo),
+            layout.block_size * 3.0,
+            36.0,
+            Color::new(1.0, 1.0, 1.0, 0.8)
+        );
+    }
+
+    if matches!(game_state.mode, GameMode::Mystery { .. }) && game_state.sim_time() < MYSTERY_INTRO {
+        draw_centered_text(layout, "MYSTERY", layout.block_size * 5.0, 36.0, GOLD);
+        for (i, name) in game_state.modifiers.names().iter().enumerate() {
+            draw_centered_text(layout, name, layout.block_size * 6.5 + i as f32 * 26.0, 24.0, WHITE);
+        }
+    }
+
+    if let GameMode::Puzzle { number } = game_state.mode {
+        if let Some(puzzle) = Puzzle::find(number) {
+            draw_centered_text(layout, puzzle.goal, layout.block_size * 3.0, 20.0, WHITE);
+        }
+    }
+
+    if game_state.game_over {
+        let title = match game_state.mode {
+            GameMode::ComboChallenge { .. } if game_state.won => "CHALLENGE COMPLETE",
+            GameMode::ComboChallenge { .. } => "COMBO BROKEN",
+            GameMode::VersusCpu { .. } if game_state.won => "YOU WIN",
+            GameMode::TwoPlayer if game_state.won => "WINNER",
+            GameMode::Puzzle { .. } if game_state.won => "SOLVED",
+            GameMode::Puzzle { .. } => "NOT SOLVED",
+            _ => "GAME OVER",
+        };
+        draw_centered_text(layout, title, layout.playfield_height() / 2.0, 32.0, WHITE);
+        draw_grade(game_state, layout, layout.playfield_height() / 2.0 - 120.0);
+        draw_record(game_state, layout, layout.playfield_height() / 2.0 - 34.0);
+        draw_max_combo(game_state, layout, layout.playfield_height() / 2.0 - 60.0);
+        draw_centered_text(
+            layout,
+            &format!("SCORE {}", game_state.score),
+            layout.playfield_height() / 2.0 + 30.0,
+            24.0,
+            WHITE
+        );
+        // Split screen has nowhere to take the S key from
+        if game_state.mode != GameMode::TwoPlayer {
+            draw_centered_text(
+                layout,
+                "S: share card  C: copy text",
+                layout.playfield_height() - layout.block_size * 2.2,
+                18.0,
+                LIGHTGRAY
+            );
+        }
+        let menu = KeyBindings::menu(game_state.settings.menu_keys);
+        draw_centered_text(
+            layout,
+            &format!("{}: restart  {}: quit", menu.key_name(Action::Restart), menu.key_name(Action::Quit)),
+            layout.playfield_height() - layout.block_size * 1.4,
+            18.0,
+            WHITE
+        );
+        draw_stack_graph(game_state, layout, layout.playfield_height() / 2.0 + 50.0);
+        if game_state.stats.preview_total > 0 {
+            draw_centered_text(
+                layout,
+                &format!("PREVIEW USED {:.0}%", game_state.stats.preview_accuracy() * 100.0),
+                layout.playfield_height() / 2.0 + 50.0 + layout.block_size * 4.0 + 24.0,
+                18.0,
+                LIGHTGRAY
+            );
+        }
+    }
+}
+
+// The game's letter grade, with the parts out of 100 underneath
+fn draw_grade(game_state: &GameState, layout: &Layout, y: f32) {
+    let score = ChallengeScore::new(&game_state.stats, game_state.board.width());
+    let color = match score.grade() {
+        'S' => GOLD,
+        'A' => GREEN,
+        'B' => SKYBLUE,
+        _ => LIGHTGRAY,
+    };
+    draw_centered_text(layout, &score.grade().to_string(), y, 64.0, color);
+    let parts: Vec<String> = score.parts().iter().map(|(label, part)| format!("{} {:.0}", label, part * 100.0)).collect();
+    draw_centered_text(layout, &parts.join(" "), y + 26.0, 16.0, LIGHTGRAY);
+}
+
+// The cleared rows sweeping off to the right, then the banner dropping in
+// and the points counting up under it
+fn draw_tetris_animation(game_state: &GameState, layout: &Layout) {
+    let Some(animation) = &game_state.tetris_animation else {
+        return;
+    };
+    let progress = animation.progress();
+    if animation.phase == AnimPhase::Sweep {
+        let shift = progress * progress * layout.playfield_width();
+        for (y, row) in &animation.rows {
+            let view_row = *y as i32 - game_state.view_top() as i32;
+            if view_row < 0 || view_row >= VISIBLE_ROWS as i32 {
+                continue;
+            }
+            for (x, cell) in row.iter().enumerate() {
+                if let Some(color) = cell.color() {
+                    draw_rectangle(
+                        (x + 1) as f32 * layout.block_size + shift,
+                        (view_row + 1) as f32 * layout.block_size,
+                        layout.block_size - 1.0,
+                        layout.block_size - 1.0,
+                        color
+                    );
+                }
+            }
+        }
+        return;
+    }
+
+    let banner_y = layout.playfield_height() / 3.0;
+    let y = match animation.phase {
+        // Eases out as it lands
+        AnimPhase::Banner => banner_y * (1.0 - (1.0 - progress).powi(2)),
+        _ => banner_y,
+    };
+    draw_centered_text(layout, "TETRIS!", y, 48.0, GOLD);
+    if animation.phase == AnimPhase::Count {
+        let shown = (animation.points as f32 * progress) as u32;
+        draw_centered_text(layout, &format!("+{}", shown), y + 36.0, 28.0, WHITE);
+    }
+}
+
+// The mode's best result, or a note that this game set a new one
+fn draw_record(game_state: &GameState, layout: &Layout, y: f32) {
+    if game_state.new_record {
+        draw_centered_text(layout, "NEW RECORD!", y, 22.0, GOLD);
+        return;
+    }
+    let Some(best) = game_state.best else {
+        return;
+    };
+    let text = match best.time {
+        Some(time) => format!("BEST {}  {:.1}s", best.score, time),
+        None => format!("BEST {}", best.score),
+    };
+    draw_centered_text(layout, &text, y, 18.0, LIGHTGRAY);
+}
+
+// This game's longest combo next to the mode's best before it
+fn draw_max_combo(game_state: &GameState, layout: &Layout, y: f32) {
+    let combo = game_state.stats.max_combo;
+    let best = game_state.best.map_or(0, |best| best.max_combo);
+    let (text, color) = if combo > best {
+        (format!("MAX COMBO {}  NEW BEST", combo), GOLD)
+    } else {
+        (format!("MAX COMBO {}  BEST {}", combo, best), LIGHTGRAY)
+    };
+    draw_centered_text(layout, &text, y, 18.0, color);
+}
+
+// Internal numbers for tuning, toggled with F3
+fn draw_debug_overlay(game_state: &GameState) {
+    let pockets = game_state.pockets();
+    let reachable_from_top = game_state.flood_fill_accessible(game_state.board.width() / 2, 0);
+    let lines = [
+        format!("tick {}", game_state.ticks),
+        format!("fall {:.3}s", game_state.effective_fall_speed()),
+        format!("lands in {} falls", game_state.ticks_until_lock(game_state.current_piece.y)),
+        format!("pockets {} smallest {}", pockets.len(), pockets.iter().min().unwrap_or(&0)),
+        format!("reachable from top {}", reachable_from_top),
+    ];
+    let x = game_state.block_size * 1.2;
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(line, x, game_state.block_size * 1.6 + i as f32 * 16.0, 16.0, GREEN);
+    }
+
+    // Holes per column, along the bottom of the playfield
+    let bottom = (game_state.board.height().min(VISIBLE_ROWS) + 1) as f32 * game_state.block_size;
+    for col in 0..game_state.board.width() {
+        let holes = game_state.count_holes_in_column(col);
+        let color = if holes > 0 { ORANGE } else { GREEN };
+        draw_text(
+            &holes.to_string(),
+            (col + 1) as f32 * game_state.block_size + game_state.block_size * 0.35,
+            bottom - 4.0,
+            16.0,
+            color
+        );
+    }
+}
+
+// Line graph of the stack height over the game, with its top edge at `y`
+fn draw_stack_graph(game_state: &GameState, layout: &Layout, y: f32) {
+    let samples = &game_state.stats.stack_heights;
+    let x = layout.block_size * 1.5;
+    let width = layout.playfield_width() - layout.block_size * 3.0;
+    let height = layout.block_size * 4.0;
+
+    draw_rectangle(x, y, width, height, Color::new(0.0, 0.0, 0.0, 0.7));
+    draw_rectangle_lines(x, y, width, height, 1.0, GRAY);
+    draw_text("STACK HEIGHT", x + 4.0, y + 14.0, 14.0, GRAY);
+    if samples.len() < 2 {
+        return;
+    }
+
+    let step = width / (samples.len() - 1) as f32;
+    let scale = height / game_state.board.height() as f32;
+    let point = |i: usize| (x + i as f32 * step, y + height - samples[i] as f32 * scale);
+    for i in 1..samples.len() {
+        let (x1, y1) = point(i - 1);
+        let (x2, y2) = point(i);
+        draw_line(x1, y1, x2, y2, 2.0, ORANGE);
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt::{self, Write};
+
+use crate::board::{Board, SizeMismatch};
+use crate::PieceType;
+
+// One locked piece: when, what and where
+#[derive(Clone)]
+pub struct ReplayMove {
+    pub time: f64,  // Simulated time of the lock
+    pub piece_type: PieceType,
+    pub x: i32,
+    pub y: i32,
+    pub shape: Vec<Vec<bool>>,
+}
+
+#[derive(Debug)]
+pub enum ReplayLoadError {
+    Io(std::io::Error),
+    Malformed,  // No `<cols>x<rows>` line to start with
+    // Recorded on a different board size than the game it's loaded into
+    Size(SizeMismatch),
+}
+
+impl fmt::Display for ReplayLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayLoadError::Io(err) => write!(f, "{}", err),
+            ReplayLoadError::Malformed => write!(f, "missing board size line"),
+            ReplayLoadError::Size(mismatch) => write!(f, "{}", mismatch),
+        }
+    }
+}
+
+impl std::error::Error for ReplayLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplayLoadError::Io(err) => Some(err),
+            ReplayLoadError::Malformed => None,
+            ReplayLoadError::Size(mismatch) => Some(mismatch),
+        }
+    }
+}
+
+// Every piece placed in a game, in order. Saved as a `<cols>x<rows>` line and
+// then one line per move: time, piece letter, x, y and the shape's rows
+// joined by `/`, e.g. `12.35 T 3 18 010/111`.
+#[derive(Clone)]
+pub struct Replay {
+    cols: usize,
+    rows: usize,
+    moves: Vec<ReplayMove>,
+}
+
+impl Replay {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self { cols, rows, moves: Vec::new() }
+    }
+
+    pub fn push(&mut self, replay_move: ReplayMove) {
+        self.moves.push(replay_move);
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}x{}\n", self.cols, self.rows);
+        for replay_move in &self.moves {
+            let rows: Vec<String> = replay_move
+                .shape
+                .iter()
+                .map(|row| row.iter().map(|&cell| if cell { '1' } else { '0' }).collect())
+                .collect();
+            let _ = writeln!(
+                text,
+                "{:.3} {} {} {} {}",
+                replay_move.time,
+                replay_move.piece_type.letter(),
+                replay_move.x,
+                replay_move.y,
+                rows.join("/")
+            );
+        }
+        text
+    }
+
+    // None if the size line is missing; move lines that don't parse are skipped
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let (cols, rows) = lines.next()?.trim().split_once('x')?;
+        let (cols, rows) = (cols.parse().ok().filter(|&cols| cols > 0)?, rows.parse().ok().filter(|&rows| rows > 0)?);
+        let mut replay = Self::new(cols, rows);
+        replay.moves = lines.filter_map(parse_move).collect();
+        Some(replay)
+    }
+
+    // Loads a replay to play alongside a `cols` x `rows` game
+    pub fn load(path: &str, cols: usize, rows: usize) -> Result<Self, ReplayLoadError> {
+        let text = std::fs::read_to_string(path).map_err(ReplayLoadError::Io)?;
+        let replay = Self::parse(&text).ok_or(ReplayLoadError::Malformed)?;
+        Board::new(replay.cols, replay.rows).check_size(cols, rows).map_err(ReplayLoadError::Size)?;
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+}
+
+fn parse_move(line: &str) -> Option<ReplayMove> {
+    let mut fields = line.split_whitespace();
+    let time = fields.next()?.parse().ok()?;
+    let letter = fields.next()?.chars().next()?;
+    let piece_type = PieceType::from_letter(letter)?;
+    let x = fields.next()?.parse().ok()?;
+    let y = fields.next()?.parse().ok()?;
+    let shape = fields.next()?.split('/').map(|row| row.chars().map(|cell| cell == '1').collect()).collect();
+    Some(ReplayMove { time, piece_type, x, y, shape })
+}
+
+// A recorded game played back next to a live one, at the same pace, for the
+// player to copy
+pub struct TrainingGhost {
+    replay: Replay,
+    pub board: Board,
+    next_move: usize,  // First move not yet placed on `board`
+}
+
+impl TrainingGhost {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            board: Board::new(replay.cols, replay.rows),
+            replay,
+            next_move: 0,
+        }
+    }
+
+    // Places every recorded move locked by simulated time `now`
+    pub fn advance(&mut self, now: f64) {
+        while let Some(replay_move) = self.replay.moves.get(self.next_move) {
+            if replay_move.time > now {
+                break;
+            }
+            // A move that doesn't fit means the file was edited or is from
+            // another version; skip it rather than write off the board
+            if self.board.shape_fits(&replay_move.shape, replay_move.x, replay_move.y) {
+                self.board.place(&replay_move.shape, replay_move.x, replay_move.y, replay_move.piece_type);
+                self.board.clear_rows();
+            }
+            self.next_move += 1;
+        }
+    }
+}
+
+// Compares a live run against a personal best's line splits: for each line
+// count, when the PB got there and when this run did
+pub struct PbPace {
+    splits: Vec<f64>,  // splits[n]: simulated time the PB cleared its (n + 1)th line
+    reached: Vec<f64>,  // The same for this run, so far
+}
+
+impl PbPace {
+    // Splits rebuilt by replaying the PB's moves onto an empty board
+    pub fn new(replay: &Replay) -> Self {
+        let mut board = Board::new(replay.cols, replay.rows);
+        let mut splits = Vec::new();
+        for replay_move in &replay.moves {
+            if !board.shape_fits(&replay_move.shape, replay_move.x, replay_move.y) {
+                continue;
+            }
+            board.place(&replay_move.shape, replay_move.x, replay_move.y, replay_move.piece_type);
+            for _ in 0..board.clear_rows() {
+                splits.push(replay_move.time);
+            }
+        }
+        Self { splits, reached: Vec::new() }
+    }
+
+    // Notes the lines this run has cleared by simulated time `now`
+    pub fn update(&mut self, lines: u32, now: f64) {
+        while self.reached.len() < lines as usize {
+            self.reached.push(now);
+        }
+    }
+
+    // Seconds this run is behind the PB at `now`, negative when ahead. Taken
+    // from the last line both cleared, or, if the PB has already cleared this
+    // run's next line, at least the time since then. None before either
+    // applies and once the run has outlasted the PB's splits.
+    pub fn delta(&self, now: f64) -> Option<f64> {
+        let lines = self.reached.len();
+        if lines > self.splits.len() {
+            return None;
+        }
+        let last_shared = lines.checked_sub(1).map(|line| self.reached[line] - self.splits[line]);
+        let waiting = self.splits.get(lines).filter(|&&split| split < now).map(|split| now - split);
+        match (last_shared, waiting) {
+            (Some(shared), Some(waiting)) => Some(shared.max(waiting)),
+            (shared, waiting) => shared.or(waiting),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_ghost_places_moves_as_their_time_comes() {
+        let replay = Replay::parse(
+            "4x4\n\
+             1.000 O 0 2 11/11\n\
+             2.000 O 0 2 11/11\n\
+             3.000 O 2 2 11/11\n",
+        )
+        .unwrap();
+        let mut ghost = TrainingGhost::new(replay);
+        ghost.advance(0.5);
+        assert_eq!(ghost.board.max_stack_height(), 0);
+
+        // The second O overlaps the first and is skipped
+        ghost.advance(2.5);
+        assert_eq!(ghost.board.max_stack_height(), 2);
+        assert_eq!(ghost.next_move, 2);
+
+        // The third completes both rows, which clear
+        ghost.advance(3.0);
+        assert_eq!(ghost.board.max_stack_height(), 0);
+        assert_eq!(ghost.next_move, 3);
+    }
+
+    #[test]
+    fn pb_pace_compares_line_splits() {
+        // The PB clears two lines at 10s and two more at 20s
+        let replay = Replay::parse(
+            "4x4\n\
+             9.000 O 0 2 11/11\n\
+             10.000 O 2 2 11/11\n\
+             19.000 O 0 2 11/11\n\
+             20.000 O 2 2 11/11\n",
+        )
+        .unwrap();
+        let mut pace = PbPace::new(&replay);
+        assert_eq!(pace.splits, [10.0, 10.0, 20.0, 20.0]);
+        assert_eq!(pace.delta(5.0), None);
+
+        // Still waiting on the first line the PB had at 10s
+        assert_eq!(pace.delta(12.0), Some(2.0));
+        pace.update(2, 12.0);
+        assert_eq!(pace.delta(13.0), Some(2.0));
+
+        // Caught up and ahead
+        pace.update(4, 18.0);
+        assert_eq!(pace.delta(18.0), Some(-2.0));
+
+        // Past the end of the PB
+        pace.update(5, 25.0);
+        assert_eq!(pace.delta(25.0), None);
+    }
+}
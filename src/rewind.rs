@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+
+use crate::board::Board;
+use crate::sequence::PieceSequence;
+use crate::{GameState, Piece, PieceType};
+
+// Seconds of simulated play between snapshots
+const SNAPSHOT_INTERVAL: f64 = 0.5;
+// How far back a rewind goes, in seconds
+const REWIND_SECONDS: f64 = 3.0;
+// Seconds before the rewind can be used again
+const REWIND_COOLDOWN: f64 = 20.0;
+
+// Everything a rewind puts back. Stats and timers keep running.
+struct Snapshot {
+    board: Board,
+    piece: Piece,
+    sequence: PieceSequence,
+    hold: Option<PieceType>,
+    hold_used: bool,
+    score: u32,
+    combo: u32,
+}
+
+// Ring buffer of recent snapshots for GameMode::Rewind
+#[derive(Default)]
+pub struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+    last_taken: Option<f64>,
+    last_used: Option<f64>,
+}
+
+impl RewindBuffer {
+    // Takes a snapshot of `game` if one is due, dropping any older than
+    // REWIND_SECONDS
+    pub fn record(&mut self, game: &GameState, now: f64) {
+        if self.last_taken.is_some_and(|taken| now - taken < SNAPSHOT_INTERVAL) {
+            return;
+        }
+        self.last_taken = Some(now);
+
+        if self.snapshots.len() as f64 > REWIND_SECONDS / SNAPSHOT_INTERVAL {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            board: game.board.clone(),
+            piece: game.current_piece.clone(),
+            sequence: game.sequence.clone(),
+            hold: game.hold,
+            hold_used: game.hold_used,
+            score: game.score,
+            combo: game.combo,
+        });
+    }
+
+    // Seconds until the rewind can be used again, 0 if it's ready
+    pub fn cooldown_left(&self, now: f64) -> f64 {
+        self.last_used.map_or(0.0, |used| (REWIND_COOLDOWN - (now - used)).max(0.0))
+    }
+
+    // Restores `game` to the oldest snapshot. Returns false while cooling down
+    // or before anything has been recorded.
+    pub fn rewind(&mut self, game: &mut GameState, now: f64) -> bool {
+        if self.cooldown_left(now) > 0.0 {
+            return false;
+        }
+        let Some(snapshot) = self.snapshots.pop_front() else {
+            return false;
+        };
+        // Snapshots are only ever taken of this game, but a grid of the wrong
+        // size would corrupt every later move
+        if let Err(err) = snapshot.board.check_size(game.board.width(), game.board.height()) {
+            eprintln!("Discarding rewind snapshot: {}", err);
+            self.snapshots.clear();
+            return false;
+        }
+
+        game.board = snapshot.board;
+        game.current_piece = snapshot.piece;
+        game.sequence = snapshot.sequence;
+        game.hold = snapshot.hold;
+        game.hold_used = snapshot.hold_used;
+        game.score = snapshot.score;
+        game.combo = snapshot.combo;
+
+        self.snapshots.clear();
+        self.last_taken = None;
+        self.last_used = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewind_restores_the_oldest_snapshot_then_cools_down() {
+        let mut game = GameState::new(10, 20);
+        let mut buffer = RewindBuffer::default();
+        assert!(!buffer.rewind(&mut game, 0.0));
+
+        buffer.record(&game, 0.0);
+        game.score = 500;
+        game.board.fill_garbage(0, 19);
+        buffer.record(&game, 0.2);  // Too soon, skipped
+        buffer.record(&game, 1.0);
+
+        assert!(buffer.rewind(&mut game, 2.0));
+        assert_eq!(game.score, 0);
+        assert!(!game.board.grid[19][0].is_filled());
+        assert_eq!(buffer.cooldown_left(2.0), REWIND_COOLDOWN);
+
+        buffer.record(&game, 3.0);
+        assert!(!buffer.rewind(&mut game, 3.0));
+        assert!(buffer.rewind(&mut game, 2.0 + REWIND_COOLDOWN));
+    }
+
+    #[test]
+    fn a_snapshot_of_another_board_size_is_discarded() {
+        let mut buffer = RewindBuffer::default();
+        buffer.record(&GameState::new(10, 20), 0.0);
+
+        let mut game = GameState::new(12, 20);
+        game.score = 300;
+        assert!(!buffer.rewind(&mut game, 1.0));
+        assert_eq!(game.score, 300);
+        assert_eq!(game.board.width(), 12);
+        assert!(!buffer.rewind(&mut game, 1.0));
+    }
+}
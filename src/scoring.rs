@@ -0,0 +1,61 @@
+use crate::settings::ScoringMode;
+
+// How a piece was moved down by the player rather than by gravity
+pub enum DropKind {
+    Soft,
+    Hard,
+}
+
+// Points for clearing `lines` rows with a single piece at `level`. Both
+// tables are multiplied by the level, classic's as on the NES, which counts
+// levels from 0 and multiplies by one more.
+pub fn line_clear_score(mode: ScoringMode, lines: u32, level: u32) -> u32 {
+    let base = match (mode, lines) {
+        (_, 0) => 0,
+        (ScoringMode::Guideline, 1) => 100,
+        (ScoringMode::Guideline, 2) => 300,
+        (ScoringMode::Guideline, 3) => 500,
+        (ScoringMode::Guideline, _) => 800,
+        (ScoringMode::Classic, 1) => 40,
+        (ScoringMode::Classic, 2) => 100,
+        (ScoringMode::Classic, 3) => 300,
+        (ScoringMode::Classic, _) => 1200,
+    };
+    base * level
+}
+
+// Points for turning a T, S, Z, L or J piece into place at `level`
+pub fn spin_bonus(level: u32) -> u32 {
+    300 * level
+}
+
+// Points for dropping a piece `cells` rows. Classic scoring predates hard
+// drops, so only soft drops count there.
+pub fn drop_score(mode: ScoringMode, kind: DropKind, cells: u32) -> u32 {
+    match (mode, kind) {
+        (_, DropKind::Soft) => cells,
+        (ScoringMode::Guideline, DropKind::Hard) => cells * 2,
+        (ScoringMode::Classic, DropKind::Hard) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classic_scoring_follows_the_nes_table() {
+        assert_eq!(line_clear_score(ScoringMode::Classic, 1, 1), 40);
+        assert_eq!(line_clear_score(ScoringMode::Classic, 4, 1), 1200);
+        assert_eq!(line_clear_score(ScoringMode::Classic, 4, 3), 3600);
+        assert_eq!(line_clear_score(ScoringMode::Guideline, 4, 3), 2400);
+        assert_eq!(line_clear_score(ScoringMode::Classic, 0, 5), 0);
+    }
+
+    #[test]
+    fn classic_scoring_only_pays_for_soft_drops() {
+        assert_eq!(drop_score(ScoringMode::Classic, DropKind::Soft, 7), 7);
+        assert_eq!(drop_score(ScoringMode::Classic, DropKind::Hard, 7), 0);
+        assert_eq!(drop_score(ScoringMode::Guideline, DropKind::Hard, 7), 14);
+    }
+}
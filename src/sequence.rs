@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+use ::rand::rngs::StdRng;
+use ::rand::seq::SliceRandom;
+
+use crate::PieceType;
+
+// Most upcoming pieces that can be previewed at once (two full bags)
+pub const MAX_PREVIEW: usize = 14;
+
+// How pieces are picked
+#[derive(Clone, Copy, PartialEq)]
+pub enum Randomizer {
+    SevenBag,  // Each run of seven is a shuffled bag of all seven types
+    Classic,  // Every piece picked at random on its own, droughts and all
+}
+
+impl Randomizer {
+    // `classic` or `random` for Classic; anything else is the 7-bag
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "classic" | "random" => Randomizer::Classic,
+            _ => Randomizer::SevenBag,
+        }
+    }
+}
+
+// Deals pieces in shuffled bags of all seven types, or one at a time under
+// Randomizer::Classic. Pieces are generated ahead of time so the preview
+// queue and the whole next bag can always be shown.
+#[derive(Clone)]
+pub struct PieceSequence {
+    rng: StdRng,
+    randomizer: Randomizer,
+    piece_override_queue: VecDeque<PieceType>,  // Dealt before anything from the bags
+    queue: VecDeque<PieceType>,  // Upcoming pieces from the bags, in order
+    bag_position: usize,  // Pieces already dealt from the current bag
+    frozen: bool,  // Only the override pieces are in play, so the bags stay hidden
+}
+
+impl PieceSequence {
+    pub fn new(rng: StdRng) -> Self {
+        let mut sequence = Self {
+            rng,
+            randomizer: Randomizer::SevenBag,
+            piece_override_queue: VecDeque::new(),
+            queue: VecDeque::new(),
+            bag_position: 0,
+            frozen: false,
+        };
+        sequence.top_up();
+        sequence
+    }
+
+    // Deals `pieces` in order, in place of any override still queued, before
+    // going back to the bags
+    pub fn set_override(&mut self, pieces: &[PieceType]) {
+        self.piece_override_queue.clear();
+        self.piece_override_queue.extend(pieces);
+    }
+
+    // Throws away the pieces generated so far and deals from `randomizer`
+    // from here on
+    pub fn set_randomizer(&mut self, randomizer: Randomizer) {
+        self.randomizer = randomizer;
+        self.queue.clear();
+        self.bag_position = 0;
+        self.top_up();
+    }
+
+    // Whether there are bags to show; Classic has none
+    pub fn deals_bags(&self) -> bool {
+        self.randomizer == Randomizer::SevenBag
+    }
+
+    // Limits the game to the override pieces queued so far
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn override_remaining(&self) -> usize {
+        self.piece_override_queue.len()
+    }
+
+    // Keeps enough bags generated to preview MAX_PREVIEW pieces plus the bag
+    // after the current one
+    fn top_up(&mut self) {
+        while self.queue.len() < MAX_PREVIEW + 7 {
+            match self.randomizer {
+                Randomizer::SevenBag => {
+                    let mut bag = PieceType::ALL;
+                    bag.shuffle(&mut self.rng);
+                    self.queue.extend(bag);
+                }
+                Randomizer::Classic => {
+                    let piece_type = *PieceType::ALL.choose(&mut self.rng).unwrap();
+                    self.queue.push_back(piece_type);
+                }
+            }
+        }
+    }
+
+    pub fn next(&mut self) -> PieceType {
+        if let Some(piece_type) = self.piece_override_queue.pop_front() {
+            return piece_type;
+        }
+
+        let piece_type = self.queue.pop_front().unwrap();
+        self.bag_position = (self.bag_position + 1) % 7;
+        self.top_up();
+        piece_type
+    }
+
+    // The next `count` pieces to be dealt, without dealing them
+    pub fn peek(&self, count: usize) -> Vec<PieceType> {
+        let bags = if self.frozen { 0 } else { count };
+        self.piece_override_queue.iter().chain(self.queue.iter().take(bags)).take(count).copied().collect()
+    }
+
+    // Pieces still to be dealt from the current bag
+    pub fn remaining_in_bag(&self) -> usize {
+        7 - self.bag_position
+    }
+
+    pub fn peek_next_bag(&self) -> [PieceType; 7] {
+        let start = self.remaining_in_bag();
+        std::array::from_fn(|i| self.queue[start + i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::rand::SeedableRng;
+
+    #[test]
+    fn the_preview_reaches_past_the_current_bag() {
+        let mut sequence = PieceSequence::new(StdRng::seed_from_u64(7));
+        sequence.next();
+        let preview = sequence.peek(MAX_PREVIEW);
+        assert_eq!(preview.len(), MAX_PREVIEW);
+        assert!(sequence.peek(MAX_PREVIEW) == preview);
+
+        let dealt: Vec<PieceType> = (0..MAX_PREVIEW).map(|_| sequence.next()).collect();
+        assert!(dealt == preview);
+    }
+
+    #[test]
+    fn a_frozen_queue_previews_only_the_override() {
+        let mut sequence = PieceSequence::new(StdRng::seed_from_u64(7));
+        sequence.set_override(&[PieceType::T, PieceType::I]);
+        assert_eq!(sequence.peek(5).len(), 5);
+
+        sequence.freeze();
+        assert!(sequence.is_frozen());
+        assert!(sequence.peek(5) == [PieceType::T, PieceType::I]);
+        assert!(sequence.next() == PieceType::T);
+        assert_eq!(sequence.override_remaining(), 1);
+        assert!(sequence.peek(5) == [PieceType::I]);
+    }
+}
@@ -0,0 +1,289 @@
+use macroquad::prelude::*;
+
+use crate::attack::{DEFAULT_ATTACK_CAP, MAX_ATTACK};
+use crate::input::MenuKeys;
+use crate::render;
+use crate::sequence::MAX_PREVIEW;
+
+// What happens to the falling piece when garbage rises underneath it.
+//
+// Either way the piece must never end up inside the stack: if the risen garbage
+// overlaps it, it is pushed straight up until it fits. If that pushes any of its
+// cells above the top of the board, the player tops out.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum GarbageShift {
+    // Piece keeps its board position and is only moved if garbage reaches it (default)
+    #[default]
+    Naive,
+    // Piece rides up with the stack, keeping its distance to the surface
+    Sticky,
+}
+
+// What a hard drop does once the piece reaches the bottom
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum HardDropLock {
+    // Piece locks on the spot (default)
+    #[default]
+    Instant,
+    // Piece lands but stays movable for HARD_DROP_LOCK_DELAY, allowing a last slide
+    Delayed,
+}
+
+// Point values for clears and drops. Both tables switch together so a game
+// never mixes the two.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ScoringMode {
+    // 100/300/500/800 per clear, 1 per soft-dropped and 2 per hard-dropped cell (default)
+    #[default]
+    Guideline,
+    // NES values: 40/100/300/1200 per clear, 1 per soft-dropped cell, nothing for hard drops
+    Classic,
+}
+
+// Keys the rotate-and-slide macro can be bound to, in the order the settings
+// screen cycles through them
+const ROTATE_SLIDE_KEYS: [KeyCode; 4] = [KeyCode::Q, KeyCode::E, KeyCode::F, KeyCode::C];
+
+// Input rate limits the settings screen cycles through, in Hz; 0 is no limit
+const INPUT_RATES: [f32; 5] = [0.0, 60.0, 120.0, 144.0, 240.0];
+
+pub struct Settings {
+    pub desperation_hint: bool,  // Flash "CLEAR TO SURVIVE" when a drop can avoid topping out
+    pub garbage_shift: GarbageShift,
+    pub zero_delays: bool,  // Practice: no waiting between a piece landing and the next one
+    pub hard_drop_lock: HardDropLock,
+    pub preview_count: usize,  // Upcoming pieces shown in the NEXT panel, up to MAX_PREVIEW
+    pub zen_limit: f64,  // Seconds without a line clear before the board starts glowing red
+    pub rotate_slide_key: Option<KeyCode>,  // Rotate then slide to the wall in one press; unbound by default
+    pub min_fall_interval: f64,  // Floor on seconds per row of gravity, overriding the level curve; 0 for none
+    pub animations: bool,  // Cosmetic motion like drop trails; off for reduced motion
+    pub garbage_delay: f64,  // Seconds received garbage waits, cancelable by clears, before rising
+    pub score_projection: bool,  // Show the best clear score the current piece can make
+    pub ghost_alpha: f32,  // Opacity of the landing preview, as a fraction of the piece color's
+    pub log_rotation_failures: bool,  // Debug: write failed rotations and the board to a log file
+    pub scoring_mode: ScoringMode,
+    pub spin_practice: bool,  // Practice: only spins score and get announced
+    pub overlay_dim: f32,  // Opacity of the black behind menus and the game over results
+    pub soft_drop_grace: f64,  // Seconds a soft drop onto the stack waits before locking; a move cancels it
+    pub dynamic_tilt: bool,  // Lean the view toward the side the piece is on
+    pub board_tilt_degrees: f32,  // How far the view leans with the piece against a wall
+    pub column_markers: bool,  // Mark the current piece's columns along the top border
+    pub isometric: bool,  // Draw locked blocks as little 3D cubes
+    pub age_colors: bool,  // Fade locked blocks toward gray as they get older
+    pub attack_cap: u32,  // Versus: most garbage lines one clear can send
+    pub animated_backgrounds: bool,  // A moving backdrop that changes with each level
+    pub max_input_hz: f32,  // Most times a second input is processed; 0 for every frame
+    pub motion_trail: bool,  // Fading copies of the piece behind it as it moves
+    pub lock_delay: f64,  // Seconds a landed piece rests before locking; moves and turns restart it
+    pub auto_hold: bool,  // Assist: hold a new piece that would cover a hole wherever it dropped
+    pub menu_keys: MenuKeys,  // From the config; shown in the game over hints
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            desperation_hint: false,
+            garbage_shift: GarbageShift::default(),
+            zero_delays: false,
+            hard_drop_lock: HardDropLock::default(),
+            preview_count: 5,
+            zen_limit: 60.0,
+            rotate_slide_key: None,
+            min_fall_interval: 0.0,
+            animations: true,
+            garbage_delay: 0.5,
+            score_projection: false,
+            ghost_alpha: 0.3,
+            log_rotation_failures: false,
+            scoring_mode: ScoringMode::default(),
+            spin_practice: false,
+            overlay_dim: 0.7,
+            soft_drop_grace: 0.0,
+            dynamic_tilt: false,
+            board_tilt_degrees: 3.0,
+            column_markers: false,
+            isometric: false,
+            age_colors: false,
+            attack_cap: DEFAULT_ATTACK_CAP,
+            animated_backgrounds: false,
+            max_input_hz: 0.0,
+            motion_trail: false,
+            lock_delay: 0.5,
+            auto_hold: false,
+            menu_keys: MenuKeys::default(),
+        }
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "On" } else { "Off" }.to_string()
+}
+
+impl Settings {
+    // Label and current value of each line on the settings screen
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Survival hint", on_off(self.desperation_hint)),
+            ("Garbage shift", match self.garbage_shift {
+                GarbageShift::Naive => "Naive",
+                GarbageShift::Sticky => "Sticky",
+            }.to_string()),
+            ("Zero-delay practice", on_off(self.zero_delays)),
+            ("Hard drop lock", match self.hard_drop_lock {
+                HardDropLock::Instant => "Instant",
+                HardDropLock::Delayed => "Delayed",
+            }.to_string()),
+            ("Preview pieces", self.preview_count.to_string()),
+            ("Zen limit", format!("{}s", self.zen_limit)),
+            ("Rotate-slide key", self.rotate_slide_key.map_or("Unset".to_string(), |key| format!("{:?}", key))),
+            ("Min fall interval", if self.min_fall_interval > 0.0 {
+                format!("{:.0}ms", self.min_fall_interval * 1000.0)
+            } else {
+                "Off".to_string()
+            }),
+            ("Animations", on_off(self.animations)),
+            ("Garbage delay", format!("{:.1}s", self.garbage_delay)),
+            ("Projected score", on_off(self.score_projection)),
+            ("Ghost opacity", format!("{:.0}%", self.ghost_alpha * 100.0)),
+            ("Log failed rotations", on_off(self.log_rotation_failures)),
+            ("Scoring", match self.scoring_mode {
+                ScoringMode::Guideline => "Guideline",
+                ScoringMode::Classic => "Classic",
+            }.to_string()),
+            ("Spin practice", on_off(self.spin_practice)),
+            ("Overlay dimming", format!("{:.0}%", self.overlay_dim * 100.0)),
+            ("Soft drop grace", format!("{:.0}ms", self.soft_drop_grace * 1000.0)),
+            ("Dynamic tilt", on_off(self.dynamic_tilt)),
+            ("Tilt angle", format!("{:.1}\u{b0}", self.board_tilt_degrees)),
+            ("Column markers", on_off(self.column_markers)),
+            ("Isometric blocks", on_off(self.isometric)),
+            ("Age colors", on_off(self.age_colors)),
+            ("Attack cap", format!("{} lines", self.attack_cap)),
+            ("Level backgrounds", on_off(self.animated_backgrounds)),
+            ("Input rate limit", if self.max_input_hz > 0.0 {
+                format!("{}Hz", self.max_input_hz)
+            } else {
+                "Off".to_string()
+            }),
+            ("Motion trail", on_off(self.motion_trail)),
+            ("Lock delay", format!("{:.0}ms", self.lock_delay * 1000.0)),
+            ("Auto-hold assist", on_off(self.auto_hold)),
+        ]
+    }
+
+    // Steps entry `index` to its next value in `direction` (-1 or 1). Toggles
+    // and choices just cycle; numbers move up or down within their range.
+    pub fn adjust(&mut self, index: usize, direction: i32) {
+        match index {
+            0 => self.desperation_hint = !self.desperation_hint,
+            1 => {
+                self.garbage_shift = match self.garbage_shift {
+                    GarbageShift::Naive => GarbageShift::Sticky,
+                    GarbageShift::Sticky => GarbageShift::Naive,
+                }
+            }
+            2 => self.zero_delays = !self.zero_delays,
+            3 => {
+                self.hard_drop_lock = match self.hard_drop_lock {
+                    HardDropLock::Instant => HardDropLock::Delayed,
+                    HardDropLock::Delayed => HardDropLock::Instant,
+                }
+            }
+            4 => {
+                self.preview_count = self.preview_count
+                    .saturating_add_signed(direction as isize)
+                    .min(MAX_PREVIEW)
+            }
+            5 => self.zen_limit = (self.zen_limit + direction as f64 * 10.0).clamp(10.0, 600.0),
+            6 => {
+                // Unset sits before the first key in the cycle
+                let options = ROTATE_SLIDE_KEYS.len() as i32 + 1;
+                let current = self.rotate_slide_key
+                    .and_then(|key| ROTATE_SLIDE_KEYS.iter().position(|&k| k == key))
+                    .map_or(0, |i| i as i32 + 1);
+                let next = (current + direction).rem_euclid(options);
+                self.rotate_slide_key = (next > 0).then(|| ROTATE_SLIDE_KEYS[next as usize - 1]);
+            }
+            7 => {
+                // Whole milliseconds, so stepping down lands on exactly 0 (off)
+                let next = ((self.min_fall_interval + direction as f64 * 0.01) * 1000.0).round() / 1000.0;
+                self.min_fall_interval = next.clamp(0.0, 0.2)
+            }
+            8 => self.animations = !self.animations,
+            9 => self.garbage_delay = (self.garbage_delay + direction as f64 * 0.5).clamp(0.0, 3.0),
+            10 => self.score_projection = !self.score_projection,
+            11 => self.ghost_alpha = (self.ghost_alpha + direction as f32 * 0.1).clamp(0.0, 1.0),
+            12 => self.log_rotation_failures = !self.log_rotation_failures,
+            13 => {
+                self.scoring_mode = match self.scoring_mode {
+                    ScoringMode::Guideline => ScoringMode::Classic,
+                    ScoringMode::Classic => ScoringMode::Guideline,
+                }
+            }
+            14 => self.spin_practice = !self.spin_practice,
+            15 => self.overlay_dim = (self.overlay_dim + direction as f32 * 0.1).clamp(0.0, 1.0),
+            16 => {
+                self.soft_drop_grace = (self.soft_drop_grace + direction as f64 * 0.01).clamp(0.0, 0.05)
+            }
+            17 => self.dynamic_tilt = !self.dynamic_tilt,
+            18 => {
+                self.board_tilt_degrees = (self.board_tilt_degrees + direction as f32 * 0.5).clamp(0.5, 10.0)
+            }
+            19 => self.column_markers = !self.column_markers,
+            20 => self.isometric = !self.isometric,
+            21 => self.age_colors = !self.age_colors,
+            22 => self.attack_cap = self.attack_cap.saturating_add_signed(direction).clamp(1, MAX_ATTACK),
+            23 => self.animated_backgrounds = !self.animated_backgrounds,
+            24 => {
+                let current = INPUT_RATES.iter().position(|&rate| rate == self.max_input_hz).unwrap_or(0) as i32;
+                let next = (current + direction).rem_euclid(INPUT_RATES.len() as i32);
+                self.max_input_hz = INPUT_RATES[next as usize];
+            }
+            25 => self.motion_trail = !self.motion_trail,
+            26 => self.lock_delay = (self.lock_delay + direction as f64 * 0.1).clamp(0.0, 1.0),
+            27 => self.auto_hold = !self.auto_hold,
+            _ => {}
+        }
+    }
+}
+
+// Overlay for changing settings mid-game. The game is paused while it's open.
+#[derive(Default)]
+pub struct SettingsScreen {
+    pub open: bool,
+    selected: usize,
+}
+
+impl SettingsScreen {
+    pub fn handle_input(&mut self, settings: &mut Settings) {
+        let count = settings.entries().len();
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % count;
+        }
+        if is_key_pressed(KeyCode::Left) {
+            settings.adjust(self.selected, -1);
+        }
+        if is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::Enter) {
+            settings.adjust(self.selected, 1);
+        }
+    }
+
+    pub fn draw(&self, settings: &Settings, x: f32, y: f32) {
+        render::draw_dim_overlay(Rect::new(0.0, 0.0, screen_width(), screen_height()), settings.overlay_dim);
+        draw_text("SETTINGS", x, y, 32.0, WHITE);
+
+        // Lines close up once there are too many to fit the window
+        let entries = settings.entries();
+        let spacing = ((screen_height() - y - 70.0) / entries.len() as f32).clamp(18.0, 28.0);
+        for (i, (label, value)) in entries.iter().enumerate() {
+            let color = if i == self.selected { YELLOW } else { LIGHTGRAY };
+            let line_y = y + 40.0 + i as f32 * spacing;
+            draw_text(label, x, line_y, 22.0, color);
+            draw_text(value, x + 260.0, line_y, 22.0, color);
+        }
+        draw_text("Tab to close", x, y + 60.0 + entries.len() as f32 * spacing, 18.0, GRAY);
+    }
+}
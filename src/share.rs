@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+use crate::GameState;
+
+const CARD_WIDTH: u32 = 600;
+const CARD_HEIGHT: u32 = 800;
+const CARD_MARGIN: f32 = 40.0;
+
+// A summary image of a finished game, composed off-screen at a fixed size so it
+// looks the same whatever the window size.
+//
+// Drawing is only flushed to the GPU at the end of a frame, so the card is
+// rendered in one frame and saved in a later one.
+pub struct ShareCard {
+    target: RenderTarget,
+    path: String,
+}
+
+impl ShareCard {
+    pub fn render(game_state: &GameState, path: String) -> Self {
+        let target = render_target(CARD_WIDTH, CARD_HEIGHT);
+        let mut camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, CARD_WIDTH as f32, CARD_HEIGHT as f32));
+        camera.render_target = Some(target.clone());
+        set_camera(&camera);
+
+        clear_background(Color::new(0.08, 0.08, 0.12, 1.0));
+        draw_text("TETRUST", CARD_MARGIN, CARD_MARGIN + 20.0, 48.0, WHITE);
+
+        // Board, scaled to fit the left part of the card
+        let board = &game_state.board;
+        let area_height = CARD_HEIGHT as f32 - CARD_MARGIN * 3.0 - 40.0;
+        let cell = (area_height / board.height() as f32).min(300.0 / board.width() as f32);
+        let board_y = CARD_MARGIN * 2.0 + 40.0;
+        draw_rectangle(
+            CARD_MARGIN,
+            board_y,
+            cell * board.width() as f32,
+            cell * board.height() as f32,
+            Color::new(0.15, 0.15, 0.15, 1.0)
+        );
+        for (y, row) in board.grid.iter().enumerate() {
+            for (x, board_cell) in row.iter().enumerate() {
+                if let Some(color) = board_cell.color() {
+                    draw_rectangle(
+                        CARD_MARGIN + x as f32 * cell,
+                        board_y + y as f32 * cell,
+                        cell - 1.0,
+                        cell - 1.0,
+                        color
+                    );
+                }
+            }
+        }
+
+        // Results next to the board
+        let stats = &game_state.stats;
+        let minutes = (stats.elapsed / 60.0) as u32;
+        let seconds = stats.elapsed % 60.0;
+        let lines = [
+            ("SCORE", game_state.score.to_string()),
+            ("LINES", stats.lines_cleared.to_string()),
+            ("TIME", format!("{}:{:05.2}", minutes, seconds)),
+            ("PPS", format!("{:.2}", stats.pps())),
+        ];
+        let text_x = CARD_MARGIN * 2.0 + cell * board.width() as f32;
+        for (i, (label, value)) in lines.iter().enumerate() {
+            let y = board_y + 30.0 + i as f32 * 80.0;
+            draw_text(label, text_x, y, 24.0, GRAY);
+            draw_text(value, text_x, y + 36.0, 40.0, WHITE);
+        }
+
+        set_default_camera();
+        Self { target, path }
+    }
+
+    pub fn save(&self) -> Result<(), image::ImageError> {
+        save_png(&self.target.texture.get_texture_data(), Path::new(&self.path))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+// Writes an image read back from the GPU to a PNG file
+pub fn save_png(image: &Image, path: &Path) -> Result<(), image::ImageError> {
+    let (width, height) = (image.width as usize, image.height as usize);
+
+    // Texture rows come back bottom-up
+    let mut bytes = Vec::with_capacity(image.bytes.len());
+    for y in (0..height).rev() {
+        bytes.extend_from_slice(&image.bytes[y * width * 4..(y + 1) * width * 4]);
+    }
+
+    image::save_buffer(path, &bytes, width as u32, height as u32, image::ColorType::Rgba8)
+}
+
+// `tetrust_screenshot_YYYYMMDD_HHMMSS.png` for the current UTC time
+pub fn screenshot_path() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "tetrust_screenshot_{:04}{:02}{:02}_{:02}{:02}{:02}.png",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// Calendar date of a day counted from 1970-01-01 (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_png_flips_the_rows_the_right_way_up() {
+        // Read back from the GPU: the bottom row, red, comes first
+        let image = Image {
+            bytes: vec![255, 0, 0, 255, 0, 0, 255, 255],
+            width: 1,
+            height: 2,
+        };
+        let path = std::env::temp_dir().join(format!("tetrust_test_{}.png", std::process::id()));
+        save_png(&image, &path).unwrap();
+        let saved = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved.dimensions(), (1, 2));
+        assert_eq!(saved.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_eq!(saved.get_pixel(0, 1).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn civil_from_days_counts_from_the_epoch() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use macroquad::audio::{load_sound, load_sound_from_bytes, play_sound_once, Sound};
+
+// Sample rate of the built-in tones
+const TONE_SAMPLE_RATE: u32 = 22050;
+
+// Something in the game that makes a noise. GameState queues these and the
+// main loop plays them.
+#[derive(Clone, Copy)]
+pub enum SoundEvent {
+    Move,
+    Rotate,
+    Lock,
+    Clear(u32),  // Lines cleared at once
+}
+
+#[derive(Debug)]
+pub enum SoundLoadError {
+    NotADirectory(PathBuf),
+    // A file exists but couldn't be loaded as a sound
+    BadFile(PathBuf, macroquad::Error),
+}
+
+impl fmt::Display for SoundLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SoundLoadError::NotADirectory(path) => write!(f, "{} is not a directory", path.display()),
+            SoundLoadError::BadFile(path, err) => write!(f, "couldn't load {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for SoundLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SoundLoadError::NotADirectory(_) => None,
+            SoundLoadError::BadFile(_, err) => Some(err),
+        }
+    }
+}
+
+// One sound for each game event
+pub struct SoundSet {
+    piece_move: Sound,
+    rotate: Sound,
+    lock: Sound,
+    clear_1: Sound,  // Also used for doubles and triples
+    clear_4: Sound,
+    game_over: Sound,
+}
+
+impl SoundSet {
+    // Loads a sound pack from `dir`. Files are named after their slot
+    // (`move.ogg`, `rotate.ogg`, `lock.ogg`, `clear1.ogg`, `clear4.ogg`,
+    // `game_over.ogg`); any that are missing get the built-in tone instead.
+    pub async fn load_from_dir(dir: &Path) -> Result<SoundSet, SoundLoadError> {
+        if !dir.is_dir() {
+            return Err(SoundLoadError::NotADirectory(dir.to_path_buf()));
+        }
+
+        Ok(SoundSet {
+            piece_move: load_slot(dir, "move.ogg", 440.0, 0.03).await?,
+            rotate: load_slot(dir, "rotate.ogg", 660.0, 0.04).await?,
+            lock: load_slot(dir, "lock.ogg", 220.0, 0.06).await?,
+            clear_1: load_slot(dir, "clear1.ogg", 880.0, 0.12).await?,
+            clear_4: load_slot(dir, "clear4.ogg", 1320.0, 0.3).await?,
+            game_over: load_slot(dir, "game_over.ogg", 110.0, 0.6).await?,
+        })
+    }
+
+    pub fn play(&self, event: SoundEvent) {
+        let sound = match event {
+            SoundEvent::Move => &self.piece_move,
+            SoundEvent::Rotate => &self.rotate,
+            SoundEvent::Lock => &self.lock,
+            SoundEvent::Clear(lines) if lines >= 4 => &self.clear_4,
+            SoundEvent::Clear(_) => &self.clear_1,
+        };
+        play_sound_once(sound);
+    }
+
+    pub fn play_game_over(&self) {
+        play_sound_once(&self.game_over);
+    }
+}
+
+// Loads `dir/file`, or a `frequency` Hz tone lasting `duration` seconds if
+// there's no such file
+async fn load_slot(dir: &Path, file: &str, frequency: f32, duration: f32) -> Result<Sound, SoundLoadError> {
+    let path = dir.join(file);
+    if path.exists() {
+        let path_str = path.to_string_lossy().into_owned();
+        return load_sound(&path_str).await.map_err(|err| SoundLoadError::BadFile(path, err));
+    }
+    load_sound_from_bytes(&tone_wav(frequency, duration))
+        .await
+        .map_err(|err| SoundLoadError::BadFile(path, err))
+}
+
+// A quiet sine beep as a 16-bit mono WAV file, fading out so it doesn't click
+fn tone_wav(frequency: f32, duration: f32) -> Vec<u8> {
+    let samples = (TONE_SAMPLE_RATE as f32 * duration) as u32;
+    let data_len = samples * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());  // fmt chunk size
+    wav.extend_from_slice(&1u16.to_le_bytes());  // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes());  // Mono
+    wav.extend_from_slice(&TONE_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(TONE_SAMPLE_RATE * 2).to_le_bytes());  // Bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes());  // Bytes per sample
+    wav.extend_from_slice(&16u16.to_le_bytes());  // Bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for i in 0..samples {
+        let t = i as f32 / TONE_SAMPLE_RATE as f32;
+        let fade = 1.0 - i as f32 / samples as f32;
+        let sample = (t * frequency * std::f32::consts::TAU).sin() * fade * 0.2;
+        wav.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    wav
+}
@@ -0,0 +1,102 @@
+use std::io::{ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+
+// Most unsent output kept for a client that isn't reading before it's dropped
+const MAX_PENDING_BYTES: usize = 1 << 20;
+
+struct Client {
+    stream: TcpStream,
+    pending: Vec<u8>,  // Written to the socket as it accepts it
+}
+
+// Sends game state to anyone who connects, one JSON object per line. Nothing
+// here ever blocks: new clients are picked up and output is written only as
+// far as the sockets take it, once per frame. Clients that hang up, error
+// or fall too far behind are dropped.
+pub struct SpectatorServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl SpectatorServer {
+    // Listens on all interfaces at `port`
+    pub fn bind(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, clients: Vec::new() })
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.clients.push(Client { stream, pending: Vec::new() });
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    eprintln!("Spectator connection failed: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Queues `line` for every connected client
+    pub fn broadcast(&mut self, line: &str) {
+        for client in &mut self.clients {
+            client.pending.extend_from_slice(line.as_bytes());
+            client.pending.push(b'\n');
+        }
+        self.clients.retain(|client| client.pending.len() <= MAX_PENDING_BYTES);
+    }
+
+    // Takes in new clients and writes what each socket will accept. Call once
+    // a frame.
+    pub fn poll(&mut self) {
+        self.accept();
+        self.clients.retain_mut(|client| {
+            while !client.pending.is_empty() {
+                match client.stream.write(&client.pending) {
+                    Ok(0) => return false,
+                    Ok(written) => {
+                        client.pending.drain(..written);
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => return false,
+                }
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn connected_clients_get_each_line() {
+        let mut server = SpectatorServer::bind(0).unwrap();
+        let port = server.listener.local_addr().unwrap().port();
+        let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        for _ in 0..100 {
+            server.poll();
+            if !server.clients.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(server.clients.len(), 1);
+
+        server.broadcast("{\"score\":100}");
+        server.broadcast("{\"score\":200}");
+        server.poll();
+        let mut lines = BufReader::new(client).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "{\"score\":100}");
+        assert_eq!(lines.next().unwrap().unwrap(), "{\"score\":200}");
+    }
+}
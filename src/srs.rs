@@ -0,0 +1,48 @@
+use crate::PieceType;
+
+// Super Rotation System. Pieces are stored as their trimmed shape with (x, y)
+// at its top-left corner, while SRS turns each piece inside a fixed box: 3x3
+// for JLSTZ, 4x4 for I and 2x2 for O. Only clockwise turns are played, so
+// only those kick tables are here. Offsets are (x, y) with y down the board.
+
+// Where the trimmed shape sits inside its box in each rotation state. The
+// spare row or column of a 3x3 box is below at spawn, left after one turn,
+// above after two and right after three.
+pub fn box_offset(piece_type: PieceType, rotation: u8) -> (i32, i32) {
+    match piece_type {
+        PieceType::O => (0, 0),
+        PieceType::I => match rotation {
+            0 => (0, 1),
+            1 => (2, 0),
+            2 => (0, 2),
+            _ => (1, 0),
+        },
+        _ => match rotation {
+            1 => (1, 0),
+            2 => (0, 1),
+            _ => (0, 0),
+        },
+    }
+}
+
+// Positions to try, in order, for a clockwise turn out of `rotation`
+const JLSTZ_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // 0 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],  // R -> 2
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],  // 2 -> L
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],  // L -> 0
+];
+const I_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+pub fn clockwise_kicks(piece_type: PieceType, rotation: u8) -> &'static [(i32, i32)] {
+    match piece_type {
+        PieceType::O => &[(0, 0)],
+        PieceType::I => &I_KICKS[rotation as usize % 4],
+        _ => &JLSTZ_KICKS[rotation as usize % 4],
+    }
+}
@@ -0,0 +1,229 @@
+use crate::board::{Board, Cell};
+use crate::json::{JsonError, JsonValue};
+use crate::{GameMode, GameState, Piece, PieceType};
+
+// Game state as JSON for outside tools: analysis dashboards, coaching
+// overlays and bots on stdin/stdout. Covers what a tool needs to pick the
+// game up: mode, board, falling piece, queue, score, stats and timers.
+// Settings, effects and the random state are not included, so a loaded game
+// carries on with fresh bags after the saved queue. Board rows and shapes are
+// strings of Cell letters and `0`/`1`. `hold` is the held piece's letter, or
+// null with nothing held.
+
+fn number(value: f64) -> JsonValue {
+    JsonValue::Number(value)
+}
+
+fn text(value: impl Into<String>) -> JsonValue {
+    JsonValue::String(value.into())
+}
+
+fn object(fields: Vec<(&str, JsonValue)>) -> JsonValue {
+    JsonValue::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+fn missing(field: &str) -> JsonError {
+    JsonError(format!("missing or invalid '{}'", field))
+}
+
+fn field<'a>(value: &'a JsonValue, path: &str) -> Result<&'a JsonValue, JsonError> {
+    path.split('.').try_fold(value, |value, key| value.get(key)).ok_or_else(|| missing(path))
+}
+
+fn field_f64(value: &JsonValue, path: &str) -> Result<f64, JsonError> {
+    field(value, path)?.as_f64().ok_or_else(|| missing(path))
+}
+
+fn field_u32(value: &JsonValue, path: &str) -> Result<u32, JsonError> {
+    Ok(field_f64(value, path)?.max(0.0) as u32)
+}
+
+fn piece_type(value: &JsonValue, path: &str) -> Result<PieceType, JsonError> {
+    value
+        .as_str()
+        .and_then(|letter| letter.chars().next())
+        .and_then(PieceType::from_letter)
+        .ok_or_else(|| missing(path))
+}
+
+impl GameState {
+    pub fn to_json(&self) -> String {
+        let piece = &self.current_piece;
+        let shape = piece
+            .shape
+            .iter()
+            .map(|row| text(row.iter().map(|&cell| if cell { '1' } else { '0' }).collect::<String>()))
+            .collect();
+        let board = self
+            .board
+            .grid
+            .iter()
+            .map(|row| text(row.iter().map(Cell::letter).collect::<String>()))
+            .collect();
+        let queue = self
+            .sequence
+            .peek(self.settings.preview_count)
+            .into_iter()
+            .map(|piece_type| text(piece_type.letter().to_string()))
+            .collect();
+
+        object(vec![
+            ("mode", text(self.mode.saved_name())),
+            ("board", JsonValue::Array(board)),
+            ("piece", object(vec![
+                ("type", text(piece.piece_type.letter().to_string())),
+                ("x", number(piece.x as f64)),
+                ("y", number(piece.y as f64)),
+                ("rotation", number(piece.rotation as f64)),
+                ("shape", JsonValue::Array(shape)),
+            ])),
+            ("queue", JsonValue::Array(queue)),
+            ("hold", self.hold.map_or(JsonValue::Null, |held| text(held.letter().to_string()))),
+            ("hold_used", JsonValue::Bool(self.hold_used)),
+            ("score", number(self.score as f64)),
+            ("level", number(self.level() as f64)),
+            ("combo", number(self.combo as f64)),
+            ("game_over", JsonValue::Bool(self.game_over)),
+            ("stats", object(vec![
+                ("pieces_placed", number(self.stats.pieces_placed as f64)),
+                ("lines_cleared", number(self.stats.lines_cleared as f64)),
+                ("garbage_lines_cleared", number(self.stats.garbage_lines_cleared as f64)),
+                ("garbage_sent", number(self.stats.garbage_sent as f64)),
+                ("max_combo", number(self.stats.max_combo as f64)),
+            ])),
+            ("timers", object(vec![
+                ("ticks", number(self.ticks as f64)),
+                ("last_fall", number(self.last_fall)),
+                ("zen", number(self.zen_timer)),
+                ("clock_left", number(self.clock_left)),
+            ])),
+        ])
+        .to_string()
+    }
+
+    // A game picking up where `to_json` left off. The board's size comes from
+    // the saved rows; the level follows from the lines cleared.
+    pub fn from_json(json: &str) -> Result<GameState, JsonError> {
+        let value = JsonValue::parse(json)?;
+
+        let rows = field(&value, "board")?.as_array().ok_or_else(|| missing("board"))?;
+        let grid = rows
+            .iter()
+            .map(|row| row.as_str().and_then(|row| row.chars().map(Cell::from_letter).collect::<Option<Vec<_>>>()))
+            .collect::<Option<Vec<_>>>()
+            .filter(|grid| !grid.is_empty() && !grid[0].is_empty() && grid.iter().all(|row| row.len() == grid[0].len()))
+            .ok_or_else(|| missing("board"))?;
+        let (cols, rows) = (grid[0].len(), grid.len());
+
+        let mut game = GameState::new(cols, rows);
+        let mode = field(&value, "mode")?.as_str().ok_or_else(|| missing("mode"))?;
+        game.set_mode(GameMode::from_name(mode));
+
+        let mut board = Board::new(cols, rows);
+        board.load_field(grid);
+        game.board = board;
+
+        let shape = field(&value, "piece.shape")?
+            .as_array()
+            .and_then(|rows| {
+                rows.iter()
+                    .map(|row| row.as_str().map(|row| row.chars().map(|cell| cell == '1').collect::<Vec<_>>()))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .filter(|shape| {
+                !shape.is_empty() && !shape[0].is_empty() && shape.iter().all(|row| row.len() == shape[0].len())
+            })
+            .ok_or_else(|| missing("piece.shape"))?;
+        game.current_piece = Piece {
+            shape,
+            x: field_f64(&value, "piece.x")? as i32,
+            y: field_f64(&value, "piece.y")? as i32,
+            piece_type: piece_type(field(&value, "piece.type")?, "piece.type")?,
+            rotation: field_u32(&value, "piece.rotation")? as u8 % 4,
+        };
+        if !game.can_move(game.current_piece.x, game.current_piece.y) {
+            return Err(JsonError("piece overlaps the board".to_string()));
+        }
+
+        let queue = field(&value, "queue")?
+            .as_array()
+            .ok_or_else(|| missing("queue"))?
+            .iter()
+            .map(|entry| piece_type(entry, "queue"))
+            .collect::<Result<Vec<_>, _>>()?;
+        game.sequence.set_override(&queue);
+        game.hold = match field(&value, "hold")? {
+            JsonValue::Null => None,
+            held => Some(piece_type(held, "hold")?),
+        };
+        game.hold_used = field(&value, "hold_used").ok().and_then(JsonValue::as_bool).unwrap_or(false);
+
+        game.score = field_u32(&value, "score")?;
+        game.combo = field_u32(&value, "combo")?;
+        game.stats.pieces_placed = field_u32(&value, "stats.pieces_placed")?;
+        game.stats.lines_cleared = field_u32(&value, "stats.lines_cleared")?;
+        game.stats.garbage_lines_cleared = field_u32(&value, "stats.garbage_lines_cleared")?;
+        game.stats.garbage_sent = field_u32(&value, "stats.garbage_sent")?;
+        game.stats.max_combo = field_u32(&value, "stats.max_combo")?;
+        game.ticks = field_f64(&value, "timers.ticks")?.max(0.0) as u64;
+        game.stats.elapsed = game.sim_time();
+        game.last_fall = field_f64(&value, "timers.last_fall")?;
+        game.zen_timer = field_f64(&value, "timers.zen")?;
+        game.clock_left = field_f64(&value, "timers.clock_left")?;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modifiers::Modifiers;
+
+    #[test]
+    fn a_game_survives_a_round_trip_through_json() {
+        let mut game = GameState::new(10, 20);
+        game.hard_drop();
+        game.hard_drop();
+        game.hold_piece();
+        for _ in 0..45 {
+            game.tick();
+        }
+        game.board.fill_garbage(0, 19);
+
+        let json = game.to_json();
+        let loaded = GameState::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json(), json);
+        assert!(loaded.hold == game.hold);
+        assert!(loaded.hold_used);
+        assert_eq!(loaded.stats.pieces_placed, 2);
+        assert_eq!(loaded.sim_time(), game.sim_time());
+        assert!(loaded.board.grid[19][0] == Cell::Garbage);
+        assert!(loaded.sequence.peek(5) == game.sequence.peek(5));
+    }
+
+    #[test]
+    fn broken_state_is_rejected() {
+        let json = GameState::new(10, 20).to_json();
+        assert!(GameState::from_json(&json.replace("\"score\"", "\"points\"")).is_err());
+        assert!(GameState::from_json(&json.replace("\"y\":0", "\"y\":25")).is_err());
+        assert!(GameState::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn a_mystery_game_resumes_with_its_rules() {
+        let mut game = GameState::new(10, 20);
+        game.set_mode(GameMode::Mystery { seed: 1234 });
+        let loaded = GameState::from_json(&game.to_json()).unwrap();
+        assert!(loaded.mode == GameMode::Mystery { seed: 1234 });
+        assert!(loaded.modifiers == Modifiers::roll(1234));
+    }
+
+    #[test]
+    fn a_ragged_piece_shape_is_rejected() {
+        let mut game = GameState::new(10, 20);
+        game.current_piece = GameState::spawn_new_piece(PieceType::T, 10);
+        let json = game.to_json();
+        assert!(json.contains("[\"010\",\"111\"]"));
+        assert!(GameState::from_json(&json.replace("[\"010\",\"111\"]", "[\"010\",\"11\"]")).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use crate::board::{Board, Cell};
+use crate::{GameMode, PieceType};
+
+// Most stack height samples kept for the game-over graph. Once full, every
+// other sample is dropped and recording slows down to match.
+const MAX_HEIGHT_SAMPLES: usize = 256;
+
+pub struct GameStats {
+    pub cleared_with: [bool; 7],  // Piece types that have triggered at least one line clear
+    pub all_pieces_cleared: bool,
+    pub pieces_placed: u32,
+    pub lines_cleared: u32,
+    pub garbage_lines_cleared: u32,  // Of lines_cleared, those that had garbage in them ("digging")
+    pub garbage_sent: u32,
+    pub sacrifices_used: u32,
+    pub max_combo: u32,
+    pub tetrises: u32,  // Clears of four lines or more
+    // Presses beyond the fewest that would have placed each piece where it went
+    pub finesse_errors: u32,
+    pub surface_entropy: u32,  // Board::surface_entropy after the last lock
+    pub elapsed: f64,  // Seconds of play so far
+    pub stack_heights: Vec<usize>,  // Max stack height sampled at piece locks, oldest first
+    // Pieces first moved before they fell from their spawn row, i.e. planned
+    // from the preview, out of all pieces the player moved
+    pub preview_used: u32,
+    pub preview_total: u32,
+    locks_per_sample: u32,
+    locks_since_sample: u32,
+}
+
+impl Default for GameStats {
+    fn default() -> Self {
+        Self {
+            cleared_with: [false; 7],
+            all_pieces_cleared: false,
+            pieces_placed: 0,
+            lines_cleared: 0,
+            garbage_lines_cleared: 0,
+            garbage_sent: 0,
+            sacrifices_used: 0,
+            max_combo: 0,
+            tetrises: 0,
+            finesse_errors: 0,
+            surface_entropy: 0,
+            elapsed: 0.0,
+            stack_heights: Vec::new(),
+            preview_used: 0,
+            preview_total: 0,
+            locks_per_sample: 1,
+            locks_since_sample: 0,
+        }
+    }
+}
+
+impl GameStats {
+    // Records a line clear triggered by `piece_type`. Returns true only on the
+    // clear that completes the set of all seven piece types.
+    pub fn record_clear(&mut self, piece_type: PieceType) -> bool {
+        self.cleared_with[piece_type.index()] = true;
+
+        if !self.all_pieces_cleared && self.cleared_with.iter().all(|&c| c) {
+            self.all_pieces_cleared = true;
+            return true;
+        }
+        false
+    }
+
+    // Called at every piece lock with the stack height after clearing
+    pub fn record_stack_height(&mut self, height: usize) {
+        self.locks_since_sample += 1;
+        if self.locks_since_sample < self.locks_per_sample {
+            return;
+        }
+        self.locks_since_sample = 0;
+
+        if self.stack_heights.len() == MAX_HEIGHT_SAMPLES {
+            let mut i = 0;
+            self.stack_heights.retain(|_| {
+                i += 1;
+                i % 2 == 0
+            });
+            self.locks_per_sample *= 2;
+        }
+        self.stack_heights.push(height);
+    }
+
+    // Called on the first input for each piece, with whether the piece was
+    // still on its spawn row
+    pub fn record_first_input(&mut self, at_spawn: bool) {
+        self.preview_total += 1;
+        if at_spawn {
+            self.preview_used += 1;
+        }
+    }
+
+    // Share of pieces the player had already decided on from the preview
+    pub fn preview_accuracy(&self) -> f32 {
+        if self.preview_total > 0 {
+            self.preview_used as f32 / self.preview_total as f32
+        } else {
+            0.0
+        }
+    }
+
+    // Counts presses beyond `fewest` for a piece that took `presses`
+    pub fn record_finesse(&mut self, presses: u32, fewest: u32) {
+        self.finesse_errors += presses.saturating_sub(fewest);
+    }
+
+    // History line for a finished game:
+    // unix time, hour of day (UTC), score, lines, pieces, seconds played,
+    // final surface entropy, letter grade
+    pub fn csv_row(&self, score: u32, grade: char) -> String {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let hour = timestamp / 3600 % 24;
+        format!(
+            "{},{},{},{},{},{:.1},{},{}",
+            timestamp, hour, score, self.lines_cleared, self.pieces_placed, self.elapsed, self.surface_entropy, grade
+        )
+    }
+
+    // Spoiler-free text to paste anywhere: four rows of `board` as colored
+    // squares, from the top of the stack down or the bottom four if it's
+    // lower than that, then the mode, score and lines
+    pub fn to_emoji_summary(&self, board: &Board, score: u32, mode: GameMode) -> String {
+        let mut summary = format!("TETRUST {}\n", mode.emoji());
+        let top = board.grid.iter().position(|row| row.iter().any(|cell| cell.is_filled())).unwrap_or(board.height());
+        let first = top.min(board.height().saturating_sub(4));
+        for row in board.grid.iter().skip(first).take(4) {
+            summary.extend(row.iter().map(|&cell| cell_emoji(cell)));
+            summary.push('\n');
+        }
+        summary + &format!("Score {} | {} lines", score, self.lines_cleared)
+    }
+
+    pub fn pieces_cleared_with(&self) -> usize {
+        self.cleared_with.iter().filter(|&&c| c).count()
+    }
+
+    // Pieces placed per second
+    pub fn pps(&self) -> f64 {
+        if self.elapsed > 0.0 {
+            self.pieces_placed as f64 / self.elapsed
+        } else {
+            0.0
+        }
+    }
+
+    // Garbage lines sent per minute
+    pub fn apm(&self) -> f64 {
+        if self.elapsed > 0.0 {
+            self.garbage_sent as f64 * 60.0 / self.elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+// Closest square emoji to each cell's color
+fn cell_emoji(cell: Cell) -> &'static str {
+    match cell {
+        Cell::Empty => "\u{2b1b}",
+        Cell::Garbage => "\u{2b1c}",
+        Cell::Piece(PieceType::I) => "\u{1fa75}",
+        Cell::Piece(PieceType::J) => "\u{1f7e6}",
+        Cell::Piece(PieceType::L) => "\u{1f7e7}",
+        Cell::Piece(PieceType::O) => "\u{1f7e8}",
+        Cell::Piece(PieceType::S) => "\u{1f7e9}",
+        Cell::Piece(PieceType::T) => "\u{1f7ea}",
+        Cell::Piece(PieceType::Z) => "\u{1f7e5}",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_summary_shows_the_bottom_rows_of_a_low_stack() {
+        let mut board = Board::new(4, 6);
+        board.fill_garbage(0, 5);
+        board.grid[4][1] = Cell::Piece(PieceType::T);
+        let stats = GameStats::default();
+        assert_eq!(
+            stats.to_emoji_summary(&board, 100, GameMode::Marathon),
+            "TETRUST \u{1f3c3}\n\
+             \u{2b1b}\u{2b1b}\u{2b1b}\u{2b1b}\n\
+             \u{2b1b}\u{2b1b}\u{2b1b}\u{2b1b}\n\
+             \u{2b1b}\u{1f7ea}\u{2b1b}\u{2b1b}\n\
+             \u{2b1c}\u{2b1b}\u{2b1b}\u{2b1b}\n\
+             Score 100 | 0 lines"
+        );
+    }
+
+    #[test]
+    fn emoji_summary_starts_at_the_top_of_a_high_stack() {
+        let mut board = Board::new(4, 6);
+        for y in 1..6 {
+            board.fill_garbage(3, y);
+        }
+        let summary = GameStats::default().to_emoji_summary(&board, 0, GameMode::Marathon);
+        let rows: Vec<&str> = summary.lines().skip(1).take(4).collect();
+        assert_eq!(rows, ["\u{2b1b}\u{2b1b}\u{2b1b}\u{2b1c}"; 4]);
+    }
+
+    #[test]
+    fn clearing_with_every_piece_type_unlocks_once() {
+        let mut stats = GameStats::default();
+        let (last, rest) = PieceType::ALL.split_last().unwrap();
+        for &piece_type in rest {
+            assert!(!stats.record_clear(piece_type));
+            assert!(!stats.record_clear(piece_type));
+        }
+        assert!(!stats.all_pieces_cleared);
+
+        assert!(stats.record_clear(*last));
+        assert!(stats.all_pieces_cleared);
+        assert!(!stats.record_clear(*last));
+    }
+
+    #[test]
+    fn rates_are_per_second_and_per_minute_of_play() {
+        let mut stats = GameStats::default();
+        assert_eq!(stats.pps(), 0.0);
+        assert_eq!(stats.apm(), 0.0);
+
+        stats.elapsed = 30.0;
+        stats.pieces_placed = 45;
+        stats.garbage_sent = 6;
+        assert_eq!(stats.pps(), 1.5);
+        assert_eq!(stats.apm(), 12.0);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::board::Cell;
+
+// How long each phase lasts, in seconds; 1.5 in all
+const SWEEP_DURATION: f32 = 0.5;
+const BANNER_DURATION: f32 = 0.4;
+const COUNT_DURATION: f32 = 0.6;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimPhase {
+    Sweep,  // The cleared rows slide off the side of the playfield
+    Banner,  // "TETRIS!" drops in from the top
+    Count,  // The points for the clear count up under the banner
+}
+
+// Played over a frozen game after a tetris. The score itself is added at the
+// lock as usual; only the counter on screen takes its time.
+pub struct TetrisAnimation {
+    timer: f32,  // Seconds into the current phase
+    pub phase: AnimPhase,
+    pub rows: Vec<(usize, Vec<Cell>)>,  // The cleared rows as they were, by board row
+    pub points: u32,
+}
+
+impl TetrisAnimation {
+    pub fn new(rows: Vec<(usize, Vec<Cell>)>, points: u32) -> Self {
+        Self { timer: 0.0, phase: AnimPhase::Sweep, rows, points }
+    }
+
+    // Moves on by `dt` seconds. Returns false once the animation is over.
+    pub fn update(&mut self, dt: f32) -> bool {
+        self.timer += dt;
+        loop {
+            let duration = self.duration();
+            if self.timer < duration {
+                return true;
+            }
+            self.timer -= duration;
+            self.phase = match self.phase {
+                AnimPhase::Sweep => AnimPhase::Banner,
+                AnimPhase::Banner => AnimPhase::Count,
+                AnimPhase::Count => return false,
+            };
+        }
+    }
+
+    fn duration(&self) -> f32 {
+        match self.phase {
+            AnimPhase::Sweep => SWEEP_DURATION,
+            AnimPhase::Banner => BANNER_DURATION,
+            AnimPhase::Count => COUNT_DURATION,
+        }
+    }
+
+    // How far through the current phase, 0 to 1
+    pub fn progress(&self) -> f32 {
+        (self.timer / self.duration()).min(1.0)
+    }
+}
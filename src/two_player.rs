@@ -0,0 +1,132 @@
+use macroquad::prelude::*;
+
+use crate::assets::Assets;
+use crate::input::{KeyBindings, MenuKeys};
+use crate::pause::{MenuCommand, PauseMenu};
+use crate::render::{self, Layout};
+use crate::{GameMode, GameState, MAX_DELTA_MS, TICK};
+
+// Two local players side by side, trading garbage
+pub struct TwoPlayerState {
+    pub p1: GameState,
+    pub p2: GameState,
+}
+
+impl TwoPlayerState {
+    pub fn new(cols: usize, rows: usize, menu_keys: MenuKeys) -> Self {
+        let mut p1 = GameState::new(cols, rows);
+        let mut p2 = GameState::new(cols, rows);
+        p1.settings.menu_keys = menu_keys;
+        p2.settings.menu_keys = menu_keys;
+        p1.set_mode(GameMode::TwoPlayer);
+        p2.set_mode(GameMode::TwoPlayer);
+        Self { p1, p2 }
+    }
+
+    fn game_over(&self) -> bool {
+        self.p1.game_over || self.p2.game_over
+    }
+
+    // Steps both boards one tick, then passes garbage across
+    fn tick(&mut self) {
+        self.p1.tick();
+        self.p2.tick();
+
+        // Sent garbage waits out the receiver's own garbage delay
+        let sent_by_p1 = self.p1.take_outgoing_garbage();
+        let sent_by_p2 = self.p2.take_outgoing_garbage();
+        self.p2.receive_garbage(sent_by_p1);
+        self.p1.receive_garbage(sent_by_p2);
+
+        // First to top out loses
+        if self.p1.game_over && !self.p2.game_over {
+            self.p2.won = true;
+            self.p2.game_over = true;
+        } else if self.p2.game_over && !self.p1.game_over {
+            self.p1.won = true;
+            self.p1.game_over = true;
+        }
+    }
+}
+
+// Split-screen game loop. Each player gets half the window: their playfield
+// and side panel, P1 on the left.
+pub async fn run(cols: usize, rows: usize, menu_keys: MenuKeys, assets: &Assets) {
+    let mut state = TwoPlayerState::new(cols, rows, menu_keys);
+    let layout = Layout::new(&state.p1);
+    let (half_width, window_height) = layout.window_size();
+    request_new_screen_size(half_width * 2.0, window_height);
+
+    let p1_bindings = KeyBindings::player_one();
+    let p2_bindings = KeyBindings::player_two();
+    let menu_bindings = KeyBindings::menu(menu_keys);
+    let mut pause_menu = PauseMenu::new();
+    let mut accumulator = 0.0;
+    loop {
+        clear_background(BLACK);
+
+        for action in menu_bindings.poll() {
+            match pause_menu.handle(action, state.game_over()) {
+                Some(MenuCommand::Restart) => {
+                    state = TwoPlayerState::new(cols, rows, menu_keys);
+                    accumulator = 0.0;
+                }
+                Some(MenuCommand::Quit) => return,
+                None => {}
+            }
+        }
+
+        // Both boards wait while either plays its tetris animation
+        if !pause_menu.is_open() {
+            let dt = get_frame_time();
+            state.p1.update_tetris_animation(dt);
+            state.p2.update_tetris_animation(dt);
+        }
+        let animating = state.p1.tetris_animation.is_some() || state.p2.tetris_animation.is_some();
+
+        if !state.game_over() && !pause_menu.is_open() && !animating {
+            state.p1.apply_actions(&p1_bindings.poll());
+            state.p2.apply_actions(&p2_bindings.poll());
+            // Split screen has no sound
+            state.p1.sound_events.clear();
+            state.p2.sound_events.clear();
+
+            accumulator += (get_frame_time() as f64).min(MAX_DELTA_MS / 1000.0);
+            while accumulator >= TICK && !state.game_over() {
+                accumulator -= TICK;
+                state.tick();
+            }
+        }
+
+        if let Some(background) = &assets.background {
+            render::draw_background(background);
+        }
+        render::draw_game_at(&state.p1, &layout, 0.0);
+        render::draw_game_at(&state.p2, &layout, half_width);
+        pause_menu.draw(&menu_bindings, state.p1.block_size, state.p1.block_size * 2.0, state.p1.settings.overlay_dim);
+
+        next_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sent_garbage_waits_out_the_receivers_delay() {
+        let mut state = TwoPlayerState::new(10, 20, MenuKeys::default());
+        state.p1.outgoing_garbage = 3;
+        state.tick();
+        assert_eq!(state.p2.pending_garbage_lines(), 3);
+        assert_eq!(state.p1.pending_garbage_lines(), 0);
+        assert!(state.p2.board.grid[19].iter().all(|cell| !cell.is_filled()));
+
+        let delay_ticks = (state.p2.settings.garbage_delay / TICK).ceil() as usize + 1;
+        for _ in 0..delay_ticks {
+            state.tick();
+        }
+        assert_eq!(state.p2.pending_garbage_lines(), 0);
+        assert!(state.p2.board.grid[17..].iter().all(|row| row.iter().any(|cell| cell.is_filled())));
+    }
+}